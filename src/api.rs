@@ -1,52 +1,104 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use futures::StreamExt;
 use jsonapi_deserialize::{deserialize_document, Document, JsonApiDeserialize};
-use log::trace;
-use post_archiver_utils::{ArchiveClient, Error, Result};
+use log::{trace, warn};
+use post_archiver_utils::{Error, Result, SemaphoreMiddleware};
 use reqwest::{
     header::{self, HeaderMap, HeaderValue},
-    Client,
+    Client, Response, StatusCode,
 };
-use tempfile::TempPath;
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_retry::{policies::ExponentialBackoff, RetryTransientMiddleware};
+use tempfile::{Builder, NamedTempFile, TempPath};
 
 use crate::{
+    bandwidth::Bandwidth,
     config::Config,
+    metrics::{Attempt, Metrics, MetricsMiddleware},
     patreon::{comment::Comment, post::Post, Member, User},
 };
 
 #[derive(Debug, Clone)]
 pub struct PatreonClient {
-    inner: ArchiveClient,
+    /// Client of the API, rate limited by `--api-rate`
+    api: ClientWithMiddleware,
+    /// Client of the media, bounded by the download concurrency and bandwidth instead
+    cdn: ClientWithMiddleware,
+    bandwidth: Bandwidth,
+    metrics: Metrics,
+    /// Folder of the downloads, until they are moved into the archive
+    staging: PathBuf,
 }
 
 impl PatreonClient {
-    pub fn new(config: &Config) -> Self {
+    /// Retries of a failed request, and of a download interrupted after the response
+    const RETRY_LIMIT: u32 = 3;
+
+    pub fn new(config: &Config, metrics: Metrics) -> Self {
         const USER_AGENT: &str =
             "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0";
 
-        let limit = config.limit() as u32;
-        let inner = ArchiveClient::builder(
-            Client::builder()
-                .user_agent(USER_AGENT)
-                .default_headers(HeaderMap::from_iter(
-                    [
-                        (header::COOKIE, config.session()),
-                        (header::ORIGIN, "https://www.patreon.com".to_owned()),
-                    ]
-                    .into_iter()
-                    .map(|(k, v)| (k, HeaderValue::from_str(&v).unwrap())),
-                ))
-                .build()
-                .unwrap(),
-            limit * 60,
-        )
-        .pre_sec_limit(limit)
-        .build();
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .default_headers(HeaderMap::from_iter(
+                [
+                    (header::COOKIE, config.session()),
+                    (header::ORIGIN, "https://www.patreon.com".to_owned()),
+                ]
+                .into_iter()
+                .map(|(k, v)| (k, HeaderValue::from_str(&v).unwrap())),
+            ))
+            .build()
+            .unwrap();
+
+        // the retries of 429s and server errors happen here only, each attempt is
+        // recorded by the metrics below them
+        let retry = || {
+            let policy = ExponentialBackoff::builder().build_with_max_retries(Self::RETRY_LIMIT);
+            RetryTransientMiddleware::new_with_policy(policy)
+        };
+
+        let rate = config.api_rate();
+        let api = ClientBuilder::new(client.clone())
+            .with(SemaphoreMiddleware::new(rate * 60, rate, rate))
+            .with(retry())
+            .with(MetricsMiddleware(metrics.clone()))
+            .build();
+        let cdn = ClientBuilder::new(client)
+            .with(retry())
+            .with(MetricsMiddleware(metrics.clone()))
+            .build();
+
+        Self {
+            api,
+            cdn,
+            bandwidth: Bandwidth::new(config),
+            metrics,
+            staging: config.staging(),
+        }
+    }
 
-        Self { inner }
+    /// Send a GET request, failing on unsuccessful statuses once the retries are spent
+    async fn send(&self, url: &str, host: Host) -> Result<Response> {
+        let client = match host {
+            Host::Api => &self.api,
+            Host::Cdn => &self.cdn,
+        };
+        let response = client
+            .get(url)
+            .with_extension(Attempt::new(matches!(host, Host::Api)))
+            .send()
+            .await?;
+        Ok(response.error_for_status()?)
     }
 
     pub async fn fetch<T: JsonApiDeserialize>(&self, url: &str) -> Result<Document<T>> {
-        let request = self.inner.get(url);
-        let response = request.send().await?;
+        let response = self.send(url, Host::Api).await?;
         let response = response.text().await?;
 
         trace!("Fetched {url}");
@@ -54,9 +106,43 @@ impl PatreonClient {
     }
 
     pub async fn download(&self, url: &str) -> Result<TempPath> {
-        let path = self.inner.download(url).await?;
-        trace!("Downloaded {url}");
-        Ok(path)
+        let mut attempt = 0;
+        loop {
+            // the failed requests are retried by the client, only the body is retried here
+            let response = self.send(url, Host::Cdn).await?;
+            match self.save_body(response).await {
+                Ok(path) => {
+                    trace!("Downloaded {url}");
+                    return Ok(path);
+                }
+                Err(e) if attempt < Self::RETRY_LIMIT => {
+                    attempt += 1;
+                    warn!(
+                        "Attempt {attempt}/{} to download {url} failed: {e}. Retrying...",
+                        Self::RETRY_LIMIT + 1
+                    );
+                    self.metrics.retry();
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn save_body(&self, response: Response) -> Result<TempPath> {
+        let mut stream = response.bytes_stream();
+
+        let mut file = staging_file(&self.staging)?;
+        let mut buffer = BufWriter::new(&mut file);
+        while let Some(bytes) = stream.next().await {
+            let bytes = bytes?;
+            self.bandwidth.consume(bytes.len()).await;
+            buffer.write_all(&bytes)?;
+        }
+        buffer.flush()?;
+        drop(buffer);
+
+        file.as_file_mut().sync_all()?;
+        Ok(file.into_temp_path())
     }
 
     pub async fn get_current_user_id(&self) -> Result<User> {
@@ -89,6 +175,15 @@ impl PatreonClient {
         )
     }
 
+    pub async fn get_post(&self, post_id: &str) -> Result<Post> {
+        let url = format!(
+            "https://www.patreon.com/api/posts/{post_id}?include=campaign,media,audio.null,audio_preview.null,poll.null,poll.choices,content_unlock_options.reward,user_defined_tags&fields[post]=comment_count,content,current_user_can_view,embed,image,post_metadata,published_at,post_type,title,url&fields[campaign]=name,url&fields[media]=id,image_urls,download_url,metadata,file_name&json-api-use-default-includes=false&json-api-version=1.0"
+        );
+        let document: Document<Post> = self.fetch(&url).await?;
+
+        Ok(document.data)
+    }
+
     pub async fn get_posts(&self, url: &str) -> Result<(Vec<Post>, Option<String>)> {
         let document: Document<Vec<Post>> = self.fetch(url).await?;
 
@@ -117,3 +212,54 @@ impl PatreonClient {
         Ok(list)
     }
 }
+
+/// Whether the request failed because the resource is deleted or not accessible anymore
+pub fn is_gone(error: &Error) -> bool {
+    let Error::Reqwest(error) = error else {
+        return false;
+    };
+    matches!(
+        error.status(),
+        Some(StatusCode::NOT_FOUND | StatusCode::FORBIDDEN | StatusCode::GONE)
+    )
+}
+
+/// Which client sends a request
+#[derive(Debug, Clone, Copy)]
+enum Host {
+    Api,
+    Cdn,
+}
+
+/// Prefix of the staged downloads, to clean up those left behind by a crashed run
+pub const STAGING_PREFIX: &str = ".patreon-archive-";
+
+/// A temp file in the staging folder, readable like a file created by `File::create`
+/// once it is renamed into the archive
+pub fn staging_file(staging: &Path) -> io::Result<NamedTempFile> {
+    fs::create_dir_all(staging)?;
+    let mut builder = Builder::new();
+    builder.prefix(STAGING_PREFIX);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(fs::Permissions::from_mode(0o666));
+    }
+    builder.tempfile_in(staging)
+}
+
+/// Remove the staged downloads of previous runs, the output must be locked
+pub fn clean_staging(staging: &Path) {
+    let Ok(entries) = fs::read_dir(staging) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(STAGING_PREFIX)
+        {
+            fs::remove_file(entry.path()).ok();
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::Config;
+
+/// Caps the bytes downloaded per second, shared by every download
+///
+/// A token bucket holding at most a second of bandwidth. The downloads take the
+/// bytes they received and may overdraw it, the debt is waited out before the next
+/// chunk so that concurrent downloads share the cap.
+#[derive(Debug, Clone, Default)]
+pub struct Bandwidth(Option<Arc<Mutex<Bucket>>>);
+
+#[derive(Debug)]
+struct Bucket {
+    rate: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl Bandwidth {
+    pub fn new(config: &Config) -> Self {
+        let Some(rate) = config.bandwidth().filter(|rate| *rate > 0) else {
+            return Self(None);
+        };
+
+        Self(Some(Arc::new(Mutex::new(Bucket {
+            rate: rate as f64,
+            available: rate as f64,
+            updated: Instant::now(),
+        }))))
+    }
+
+    /// Take the received bytes, waiting while the bucket is overdrawn
+    pub async fn consume(&self, bytes: usize) {
+        let Some(bucket) = &self.0 else {
+            return;
+        };
+
+        let wait = {
+            let mut bucket = bucket.lock().unwrap();
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.available = (bucket.available + elapsed * bucket.rate).min(bucket.rate);
+            bucket.updated = now;
+
+            bucket.available -= bytes as f64;
+            (bucket.available < 0.0).then(|| -bucket.available / bucket.rate)
+        };
+
+        if let Some(wait) = wait {
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
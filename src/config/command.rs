@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use clap::{Args, Subcommand};
+
+/// Commands working on an archive, without the session
+#[derive(Debug, Clone, Subcommand)]
+pub enum Command {
+    /// Render the archive as a static HTML site
+    ExportHtml(ExportHtml),
+    /// Write every post as Markdown with front matter, e.g. for Obsidian
+    ExportMarkdown(ExportMarkdown),
+    /// Bind the posts of a creator into an EPUB book
+    ExportEpub(ExportEpub),
+    /// Pack the images of posts into CBZ comic books
+    ExportCbz(ExportCbz),
+    /// Import a folder downloaded by gallery-dl or PatreonDownloader
+    ImportDir(ImportDir),
+    /// Hard link the files with identical content in an existing archive
+    Dedupe(Dedupe),
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ArchiveArgs {
+    /// Folder of the archive
+    #[arg(long, default_value = "./archive", env = "OUTPUT")]
+    pub archive: PathBuf,
+}
+
+/// Posts of a creator, in the order they were published
+#[derive(Debug, Clone, Args)]
+pub struct SelectArgs {
+    /// Campaign id or name of the creator
+    #[arg(long)]
+    pub creator: String,
+    /// Only the posts of the collection
+    #[arg(long)]
+    pub collection: Option<String>,
+    /// Only the posts published since the day, e.g. `2024-01-31`
+    #[arg(long)]
+    pub since: Option<NaiveDate>,
+    /// Only the posts published until the day, included
+    #[arg(long)]
+    pub until: Option<NaiveDate>,
+    /// Only the posts, by Patreon id or url
+    #[arg(long)]
+    pub post: Vec<String>,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportHtml {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    /// Folder of the site
+    pub dest: PathBuf,
+    /// Posts listed on every page
+    #[arg(long, default_value = "20")]
+    pub page_size: usize,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportMarkdown {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    /// Folder of the notes
+    pub dest: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportEpub {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    #[command(flatten)]
+    pub select: SelectArgs,
+    /// Title of the book, the collection or the creator by default
+    #[arg(long)]
+    pub title: Option<String>,
+    /// Language of the book
+    #[arg(long, default_value = "en")]
+    pub language: String,
+    /// The EPUB file
+    pub dest: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ExportCbz {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    #[command(flatten)]
+    pub select: SelectArgs,
+    /// A book for every post, written into the `DEST` folder
+    #[arg(long)]
+    pub split: bool,
+    /// The CBZ file, or the folder with `--split`
+    pub dest: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct ImportDir {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    /// Campaign id of the posts, when the sidecars do not have it
+    #[arg(long)]
+    pub campaign: Option<String>,
+    /// Move the files into the archive, instead of hard linking them
+    #[arg(long = "move")]
+    pub move_files: bool,
+    /// The folder to import, searched recursively for JSON sidecars
+    pub source: PathBuf,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct Dedupe {
+    #[command(flatten)]
+    pub archive: ArchiveArgs,
+    /// Only count the duplicates, without linking them
+    #[arg(long)]
+    pub dry_run: bool,
+}
@@ -1,20 +1,30 @@
+pub mod command;
 pub mod save_type;
 
+use command::Command;
+
 use clap::{Parser, ValueEnum};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use dotenv::dotenv;
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use indicatif_log_bridge::LogWrapper;
 use serde::{Deserialize, Serialize};
-use std::{ops::Deref, path::PathBuf};
+use std::{io, net::SocketAddr, ops::Deref, path::PathBuf, time::Duration};
 
-use crate::patreon::{post::Post, Member};
+use crate::{
+    disk::parse_size,
+    event::{Event, EventSink, Totals},
+    metrics::Metrics,
+    patreon::{post::Post, Member},
+    watch::parse_creator_interval,
+};
 
 #[derive(Debug, Clone, Parser, Default)]
+#[command(subcommand_negates_reqs = true)]
 pub struct Config {
     /// Your `session_id` cookie
-    #[clap(env = "SESSION")]
-    session: String,
+    #[clap(env = "SESSION", required = true)]
+    session: Option<String>,
     /// Which you path want to save
     #[arg(default_value = "./archive", env = "OUTPUT")]
     output: PathBuf,
@@ -27,12 +37,71 @@ pub struct Config {
     /// Blacklist of creator IDs
     #[arg(short, long, num_args = 0..)]
     blacklist: Vec<String>,
-    /// Limit download concurrency
-    #[arg(long, default_value = "20")]
-    limit: usize,
+    /// Patreon API requests per second
+    #[arg(long, alias = "limit", default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+    api_rate: u32,
+    /// Files downloaded at once from the CDN
+    #[arg(long, default_value = "8", value_parser = clap::value_parser!(u32).range(1..))]
+    download_concurrency: u32,
+    /// Posts downloading their files at once
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    post_concurrency: u32,
+    /// Cap the download bandwidth per second, e.g. `20MB`
+    #[arg(long, value_parser = parse_size)]
+    bandwidth: Option<u64>,
     /// Skip free post
     #[arg(long, name = "skip-free")]
     skip_free: bool,
+    /// Write the summary report as JSON
+    #[arg(long)]
+    report: Option<PathBuf>,
+    /// How to display progress
+    #[arg(long, default_value = "bar")]
+    progress: ProgressMode,
+    /// Write JSON progress events to a file instead of stdout
+    #[arg(long)]
+    progress_file: Option<PathBuf>,
+    /// Stay resident and sync again after the interval, e.g. `6h`
+    #[arg(long, value_parser = humantime::parse_duration)]
+    watch: Option<Duration>,
+    /// Random delay added to every watch interval
+    #[arg(long, default_value = "5m", value_parser = humantime::parse_duration)]
+    watch_jitter: Duration,
+    /// Watch interval of a busy creator, e.g. `somecreator=30m`
+    #[arg(long, value_parser = parse_creator_interval)]
+    watch_creator: Vec<(String, Duration)>,
+    /// Serve the control API in watch mode
+    #[arg(long, num_args = 0..=1, default_missing_value = "127.0.0.1:8787", requires = "watch")]
+    listen: Option<SocketAddr>,
+    /// Send a JSON payload to the URL for every newly archived post
+    #[arg(long)]
+    webhook: Option<String>,
+    /// Webhook body template, e.g. for Discord/Slack (see README)
+    #[arg(long, requires = "webhook")]
+    webhook_template: Option<PathBuf>,
+    /// Shell command run after every archived post, with JSON on stdin
+    #[arg(long)]
+    post_hook: Option<String>,
+    /// Shell command run after every saved file, with JSON on stdin
+    #[arg(long)]
+    file_hook: Option<String>,
+    /// Write Atom and podcast feeds of every creator to the `feeds` folder after each sync
+    #[arg(long)]
+    feed: bool,
+    /// Public URL of the output folder, to link the files in the feeds
+    #[arg(long, requires = "feed")]
+    feed_base_url: Option<String>,
+    /// Hard link the files with identical content, indexed by their SHA-256
+    #[arg(long)]
+    dedupe: bool,
+    /// Folder of the downloads in progress [default: <OUTPUT>/configs/downloads]
+    #[arg(long)]
+    staging_dir: Option<PathBuf>,
+    /// Pause new downloads while the output or staging folder has less free space, e.g. `10GiB`
+    #[arg(long, value_parser = parse_size)]
+    min_free_space: Option<u64>,
+    #[command(subcommand)]
+    command: Option<Command>,
     #[command(flatten)]
     pub verbose: Verbosity<InfoLevel>,
     #[clap(skip)]
@@ -58,17 +127,77 @@ impl Config {
     }
     /// Get the session cookie
     pub fn session(&self) -> String {
-        if self.session.starts_with("session_id=") {
-            self.session.clone()
+        // only missing with a command, which never needs the session
+        let session = self.session.as_deref().unwrap_or_default();
+        if session.starts_with("session_id=") {
+            session.to_string()
         } else {
-            format!("session_id={}", self.session)
+            format!("session_id={session}")
         }
     }
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
     pub const fn output(&self) -> &PathBuf {
         &self.output
     }
-    pub const fn limit(&self) -> usize {
-        self.limit
+    /// Folder of the downloads in progress, on the output filesystem by default so that
+    /// they are renamed into the archive rather than copied
+    pub fn staging(&self) -> PathBuf {
+        self.staging_dir
+            .clone()
+            .unwrap_or_else(|| self.output.join("configs/downloads"))
+    }
+    pub const fn min_free_space(&self) -> Option<u64> {
+        self.min_free_space
+    }
+    pub const fn api_rate(&self) -> u32 {
+        self.api_rate
+    }
+    pub const fn download_concurrency(&self) -> usize {
+        self.download_concurrency as usize
+    }
+    pub const fn post_concurrency(&self) -> usize {
+        self.post_concurrency as usize
+    }
+    pub const fn bandwidth(&self) -> Option<u64> {
+        self.bandwidth
+    }
+    pub fn report(&self) -> Option<&PathBuf> {
+        self.report.as_ref()
+    }
+    pub const fn watch(&self) -> Option<Duration> {
+        self.watch
+    }
+    pub const fn watch_jitter(&self) -> Duration {
+        self.watch_jitter
+    }
+    pub fn watch_creators(&self) -> &[(String, Duration)] {
+        &self.watch_creator
+    }
+    pub const fn listen(&self) -> Option<SocketAddr> {
+        self.listen
+    }
+    pub fn webhook(&self) -> Option<&str> {
+        self.webhook.as_deref()
+    }
+    pub fn webhook_template(&self) -> Option<&PathBuf> {
+        self.webhook_template.as_ref()
+    }
+    pub fn post_hook(&self) -> Option<&str> {
+        self.post_hook.as_deref()
+    }
+    pub fn file_hook(&self) -> Option<&str> {
+        self.file_hook.as_deref()
+    }
+    pub const fn feed(&self) -> bool {
+        self.feed
+    }
+    pub fn feed_base_url(&self) -> Option<&str> {
+        self.feed_base_url.as_deref()
+    }
+    pub const fn dedupe(&self) -> bool {
+        self.dedupe
     }
 
     pub fn filter_member(&self, member: &Member) -> bool {
@@ -125,6 +254,15 @@ impl Strategy {
     }
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum, PartialEq, Eq, Default)]
+pub enum ProgressMode {
+    /// Interactive progress bars
+    #[default]
+    Bar,
+    /// JSON lines events, for running under systemd or in containers
+    Json,
+}
+
 #[derive(Debug, Clone)]
 pub struct Progress(ProgressBar);
 
@@ -159,14 +297,63 @@ pub struct ProgressSet {
     pub creators: Progress,
     pub posts: Progress,
     pub files: Progress,
+    events: EventSink,
+    metrics: Metrics,
 }
 
 impl ProgressSet {
-    pub fn new(config: &Config) -> Self {
-        Self {
+    pub fn new(config: &Config, metrics: Metrics) -> io::Result<Self> {
+        let events = match config.progress {
+            ProgressMode::Bar => EventSink::default(),
+            ProgressMode::Json => {
+                config.multi.set_draw_target(ProgressDrawTarget::hidden());
+                match &config.progress_file {
+                    Some(path) => EventSink::file(path)?,
+                    None => EventSink::stdout(),
+                }
+            }
+        };
+
+        Ok(Self {
             creators: config.progress("creators"),
             posts: config.progress("posts"),
             files: config.progress("files"),
+            events,
+            metrics,
+        })
+    }
+
+    /// Replace the bars with fresh ones for the next watch round, keeping the event sink
+    pub fn reset(&mut self, config: &Config) {
+        for pb in [&self.creators, &self.posts, &self.files] {
+            pb.finish_and_clear();
+            config.multi.remove(pb);
         }
+        self.creators = config.progress("creators");
+        self.posts = config.progress("posts");
+        self.files = config.progress("files");
+    }
+
+    pub fn events(&self) -> &EventSink {
+        &self.events
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn emit(&self, event: Event) {
+        self.metrics.observe(&event);
+        if !self.events.is_enabled() {
+            return;
+        }
+
+        let count = |pb: &Progress| (pb.position(), pb.length().unwrap_or_default());
+        let totals = Totals {
+            creators: count(&self.creators),
+            posts: count(&self.posts),
+            files: count(&self.files),
+        };
+        self.events.emit(&event, totals);
     }
 }
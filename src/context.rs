@@ -1,11 +1,26 @@
-use std::{fs::read_to_string, path::Path};
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 
+use crate::fs::atomic_write_json;
+
+/// Cloning shares the underlying maps, e.g. with the control server
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Context {
-    pub campaigns: DashMap<String, CachedCampaign>,
+    pub campaigns: Arc<DashMap<String, CachedCampaign>>,
+    /// Posts which failed to import, keyed by post id
+    #[serde(default)]
+    pub failures: Arc<DashMap<String, FailedPost>>,
+    /// Archived files by Patreon media id, relative to the output folder
+    #[serde(default)]
+    pub media: Arc<DashMap<String, PathBuf>>,
 }
 
 impl Context {
@@ -17,11 +32,60 @@ impl Context {
         serde_json::from_str(&json).unwrap_or_default()
     }
 
-    pub fn save(&self, path: &Path) {
-        let path = path.join(Self::RELATION_PATH);
-        std::fs::create_dir_all(path.parent().unwrap()).expect("Failed to create context folder");
-        let json = serde_json::to_string(self).expect("Failed to serialize context");
-        std::fs::write(path, json).expect("Failed to save context");
+    /// Persist the context, writing to a temp file first and renaming it into place
+    /// so that an interrupted save never leaves a truncated file behind.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        atomic_write_json(&path.join(Self::RELATION_PATH), self)
+    }
+
+    /// Record the failed import of the post. Returns the attempts so far.
+    pub fn record_failure(
+        &self,
+        post: &str,
+        campaign: &str,
+        reason: String,
+        files: Vec<String>,
+    ) -> u32 {
+        let mut failure = self.failures.entry(post.to_string()).or_default();
+        failure.campaign = campaign.to_string();
+        failure.reason = reason;
+        failure.files = files;
+        failure.attempts += 1;
+        failure.attempts
+    }
+
+    /// Count a failed fetch of the post, keeping its files. Returns the attempts so far.
+    pub fn record_attempt(&self, post: &str, reason: String) -> u32 {
+        let Some(mut failure) = self.failures.get_mut(post) else {
+            return 0;
+        };
+        failure.reason = reason;
+        failure.attempts += 1;
+        failure.attempts
+    }
+
+    pub fn resolve_failure(&self, post: &str) {
+        self.failures.remove(post);
+    }
+
+    pub fn record_media(&self, id: &str, path: &Path, output: &Path) {
+        let path = path.strip_prefix(output).unwrap_or(path);
+        self.media.insert(id.to_string(), path.to_path_buf());
+    }
+
+    /// The archived file of the media, when it is still there
+    pub fn archived_media(&self, id: &str, output: &Path) -> Option<PathBuf> {
+        let path = output.join(self.media.get(id)?.value());
+        path.is_file().then_some(path)
+    }
+
+    /// Ids of the failed posts which belong to the campaign
+    pub fn failed_posts(&self, campaign: &str) -> HashSet<String> {
+        self.failures
+            .iter()
+            .filter(|failure| failure.campaign == campaign)
+            .map(|failure| failure.key().clone())
+            .collect()
     }
 }
 
@@ -32,6 +96,10 @@ pub struct CachedCampaign {
 }
 
 impl CachedCampaign {
+    pub const fn new(published: i64, cents: u32) -> Self {
+        Self { published, cents }
+    }
+
     pub fn last_published(&self, cents: u32) -> Option<i64> {
         let cents_unchanged = cents <= self.cents;
         cents_unchanged.then_some(self.published)
@@ -46,3 +114,18 @@ impl CachedCampaign {
         }
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct FailedPost {
+    pub campaign: String,
+    pub reason: String,
+    /// Urls of the files which could not be downloaded or saved
+    #[serde(default)]
+    pub files: Vec<String>,
+    pub attempts: u32,
+}
+
+impl FailedPost {
+    /// Attempts before a post which cannot be fetched anymore is given up
+    pub const MAX_ATTEMPTS: u32 = 5;
+}
@@ -0,0 +1,118 @@
+use std::{convert::Infallible, io, net::SocketAddr};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{
+        sse::{Event as SseEvent, KeepAlive},
+        Sse,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::{stream, Stream};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpListener, sync::broadcast::error::RecvError};
+
+use crate::{context::Context, event::EventSink, metrics::Metrics, watch::SyncQueue};
+
+/// Embedded HTTP server to monitor and trigger syncs in watch mode
+#[derive(Debug, Clone)]
+pub struct ControlServer {
+    pub context: Context,
+    pub queue: SyncQueue,
+    pub events: EventSink,
+    pub metrics: Metrics,
+}
+
+#[derive(Debug, Serialize)]
+struct CampaignState {
+    id: String,
+    /// Timestamp of the latest archived post
+    published: i64,
+    cents: u32,
+    /// Posts waiting to be retried
+    failures: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncRequest {
+    campaigns: Vec<String>,
+}
+
+impl ControlServer {
+    pub async fn spawn(self, addr: SocketAddr) -> io::Result<()> {
+        let app = Router::new()
+            .route("/campaigns", get(list_campaigns))
+            .route("/sync", post(queue_sync))
+            .route("/events", get(stream_events))
+            .route("/metrics", get(render_metrics))
+            .with_state(self);
+
+        let listener = TcpListener::bind(addr).await?;
+        info!("Control server listening on http://{addr}");
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Control server stopped: {e}");
+            }
+        });
+
+        Ok(())
+    }
+}
+
+async fn list_campaigns(State(server): State<ControlServer>) -> Json<Vec<CampaignState>> {
+    let mut campaigns = server
+        .context
+        .campaigns
+        .iter()
+        .map(|campaign| CampaignState {
+            id: campaign.key().clone(),
+            published: campaign.published,
+            cents: campaign.cents,
+            failures: server.context.failed_posts(campaign.key()).len(),
+        })
+        .collect::<Vec<_>>();
+    campaigns.sort_by(|a, b| a.id.cmp(&b.id));
+    Json(campaigns)
+}
+
+async fn queue_sync(
+    State(server): State<ControlServer>,
+    Json(request): Json<SyncRequest>,
+) -> (StatusCode, Json<Vec<String>>) {
+    // only the tracked campaigns, an unknown one would stay due forever
+    let unknown = request
+        .campaigns
+        .iter()
+        .filter(|campaign| !server.context.campaigns.contains_key(*campaign))
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        return (StatusCode::NOT_FOUND, Json(unknown));
+    }
+
+    info!("Sync requested: {}", request.campaigns.join(", "));
+    server.queue.push(request.campaigns.clone());
+    (StatusCode::ACCEPTED, Json(request.campaigns))
+}
+
+async fn stream_events(
+    State(server): State<ControlServer>,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let events = stream::unfold(server.events.subscribe(), |mut rx| async move {
+        loop {
+            match rx.recv().await {
+                Ok(line) => return Some((Ok(SseEvent::default().data(line)), rx)),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn render_metrics(State(server): State<ControlServer>) -> String {
+    server.metrics.render()
+}
@@ -12,7 +12,10 @@ use post_archiver_utils::Result;
 use crate::{
     api::PatreonClient,
     config::{Config, ProgressSet},
+    metrics::Pipeline,
     patreon::{Campaign, Member, User},
+    shutdown::Shutdown,
+    watch::Schedule,
 };
 
 pub async fn list_members(
@@ -21,10 +24,17 @@ pub async fn list_members(
     config: &Config,
     client: &PatreonClient,
     pb: &ProgressSet,
+    schedule: &Schedule,
+    shutdown: &Shutdown,
 ) {
     info!("Loading Member List");
-    let Ok(mut members) = client.get_members(user).await else {
+    let members = tokio::select! {
+        members = client.get_members(user) => members,
+        _ = shutdown.wait() => return,
+    };
+    let Ok(mut members) = members else {
         error!("Failed to load user data");
+        schedule.fail();
         return;
     };
 
@@ -32,18 +42,26 @@ pub async fn list_members(
     members.retain(|c| config.filter_member(c));
     let filtered = members.len();
     let excluded = total - filtered;
+    members.retain(|c| schedule.is_due(c));
+    let pending = filtered - members.len();
     info!("");
     info!("Total: {total} members");
     info!("Excluded: {excluded} members");
     info!("Included: {filtered} members");
+    if pending > 0 {
+        info!("Not due yet: {pending} members");
+    }
     info!("");
 
     if log::log_enabled!(log::Level::Info) {
         display_members(&members);
     }
 
-    pb.creators.inc_length(filtered as u64);
+    pb.creators.inc_length(members.len() as u64);
+    pb.metrics()
+        .queued(Pipeline::Campaign, members.len() as i64);
     for member in members {
+        schedule.synced(&member);
         campaign_pipeline.send(member).unwrap();
     }
 }
@@ -0,0 +1,177 @@
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use dashmap::DashMap;
+use log::{debug, info, warn};
+use post_archiver_utils::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config::command::Dedupe, fs::atomic_write_json, lock::ArchiveLock, report::format_bytes,
+};
+
+/// Archived files by the SHA-256 of their content, so that a file posted again is
+/// stored once and hard linked into every post
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HashIndex {
+    /// Paths are relative to the output folder
+    files: Arc<DashMap<String, PathBuf>>,
+}
+
+impl HashIndex {
+    pub const RELATION_PATH: &'static str = "configs/patreon-archive-hashes.json";
+
+    pub fn load(path: &Path) -> Self {
+        let path = path.join(Self::RELATION_PATH);
+        let json = fs::read_to_string(path).unwrap_or_default();
+        serde_json::from_str(&json).unwrap_or_default()
+    }
+
+    /// Persist the index, through a temp file renamed into place like the context
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        atomic_write_json(&path.join(Self::RELATION_PATH), self)
+    }
+
+    /// Index the file, or replace it by a hard link to an archived file with the same
+    /// content. Returns the bytes saved, 0 when the content is new.
+    pub fn store(&self, output: &Path, path: &Path) -> io::Result<u64> {
+        self.deduplicate(output, path, false)
+    }
+
+    /// Only counted without linking with `dry_run`
+    fn deduplicate(&self, output: &Path, path: &Path, dry_run: bool) -> io::Result<u64> {
+        let hash = hash_file(path)?;
+        let relative = path.strip_prefix(output).unwrap_or(path).to_path_buf();
+
+        // the entry is dropped before touching the filesystem again
+        let existing = self.files.get(&hash).map(|entry| entry.clone());
+        let original = match existing {
+            Some(existing) if existing != relative => output.join(existing),
+            Some(_) => return Ok(0),
+            None => {
+                self.files.insert(hash, relative);
+                return Ok(0);
+            }
+        };
+
+        if same_file(&original, path) {
+            return Ok(0);
+        }
+        // removed or changed since it was indexed, even when the size is the same
+        let size = path.metadata()?.len();
+        let original_hash = original
+            .metadata()
+            .is_ok_and(|meta| meta.len() == size)
+            .then(|| hash_file(&original).ok())
+            .flatten();
+        if original_hash.as_ref() != Some(&hash) {
+            if let Some(original_hash) = original_hash {
+                let original = original.strip_prefix(output).unwrap_or(&original);
+                self.files.insert(original_hash, original.to_path_buf());
+            }
+            self.files.insert(hash, relative);
+            return Ok(0);
+        }
+
+        if dry_run {
+            return Ok(size);
+        }
+        link(&original, path)?;
+        debug!("Deduplicated {} -> {}", path.display(), original.display());
+        Ok(size)
+    }
+}
+
+/// Replace `path` by a hard link to `original`, through a temp link renamed over it
+fn link(original: &Path, path: &Path) -> io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".dedupe");
+    let temp = path.with_file_name(name);
+
+    fs::remove_file(&temp).ok();
+    fs::hard_link(original, &temp)?;
+    fs::rename(&temp, path).inspect_err(|_| {
+        fs::remove_file(&temp).ok();
+    })
+}
+
+/// Whether the paths are hard links of the same file
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (a.metadata(), b.metadata()) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// Whether the paths are hard links of the same file, always relinked off unix
+#[cfg(not(unix))]
+fn same_file(_: &Path, _: &Path) -> bool {
+    false
+}
+
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Hash every file of an existing archive, and hard link the duplicates
+pub fn dedupe(args: &Dedupe) -> Result<()> {
+    let output = &args.archive.archive;
+    let _lock = ArchiveLock::acquire(output)?;
+    let index = HashIndex::load(output);
+
+    // the posts are in `<chunk>/<index>/`, the configs and feeds are left out
+    let mut files = vec![];
+    for entry in fs::read_dir(output)? {
+        let path = entry?.path();
+        let is_chunk = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.chars().all(|c| c.is_ascii_digit()));
+        if path.is_dir() && is_chunk {
+            walk(&path, &mut files)?;
+        }
+    }
+    files.sort();
+    info!("Hashing {} files", files.len());
+
+    let (mut linked, mut saved) = (0, 0);
+    for path in files {
+        match index.deduplicate(output, &path, args.dry_run) {
+            Ok(0) => {}
+            Ok(bytes) => {
+                linked += 1;
+                saved += bytes;
+            }
+            Err(e) => warn!("Failed to deduplicate {}: {e}", path.display()),
+        }
+    }
+
+    if args.dry_run {
+        info!("{linked} duplicated files, {} to save", format_bytes(saved));
+    } else {
+        index.save(output)?;
+        info!("{linked} files hard linked, {} saved", format_bytes(saved));
+    }
+    Ok(())
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
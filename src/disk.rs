@@ -0,0 +1,146 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{report::format_bytes, shutdown::Shutdown, Config};
+
+/// Pauses the new downloads while the staging or the output folder is short of space
+///
+/// The downloads in progress go on, the threshold is a margin rather than a quota.
+#[derive(Debug, Clone, Default)]
+pub struct SpaceGuard(Option<Arc<SpaceGuardInner>>);
+
+#[derive(Debug)]
+struct SpaceGuardInner {
+    paths: Vec<PathBuf>,
+    min_free: u64,
+    paused: AtomicBool,
+}
+
+impl SpaceGuard {
+    const INTERVAL: Duration = Duration::from_secs(30);
+
+    pub fn new(config: &Config) -> Self {
+        let Some(min_free) = config.min_free_space() else {
+            return Self(None);
+        };
+        if cfg!(not(unix)) {
+            warn!("`--min-free-space` is only supported on unix, ignoring it");
+            return Self(None);
+        }
+
+        Self(Some(Arc::new(SpaceGuardInner {
+            paths: vec![config.output().clone(), config.staging()],
+            min_free,
+            paused: AtomicBool::new(false),
+        })))
+    }
+
+    /// Resolves once there is enough free space, false when a shutdown is requested first
+    pub async fn wait(&self, shutdown: &Shutdown) -> bool {
+        let Some(inner) = &self.0 else {
+            return true;
+        };
+
+        loop {
+            let free = inner.paths.iter().filter_map(|path| free_space(path)).min();
+            match free {
+                Some(free) if free < inner.min_free => {
+                    if !inner.paused.swap(true, Ordering::SeqCst) {
+                        warn!(
+                            "Downloads paused, {} free of the {} required",
+                            format_bytes(free),
+                            format_bytes(inner.min_free)
+                        );
+                    }
+                }
+                _ => {
+                    if inner.paused.swap(false, Ordering::SeqCst) {
+                        info!("Downloads resumed");
+                    }
+                    return true;
+                }
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(Self::INTERVAL) => {},
+                _ = shutdown.wait() => return false,
+            }
+        }
+    }
+}
+
+/// Bytes available to the user on the filesystem of the path
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    let stat = rustix::fs::statvfs(path).ok()?;
+    Some(stat.f_bavail.saturating_mul(stat.f_frsize))
+}
+
+#[cfg(not(unix))]
+fn free_space(_: &Path) -> Option<u64> {
+    None
+}
+
+/// A size such as `500MB` or `10GiB`, the bare units are binary, e.g. `10G`
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number
+        .parse::<f64>()
+        .map_err(|_| format!("expected a size such as `10GiB`, got `{value}`"))?;
+
+    let unit = unit.trim().to_ascii_lowercase();
+    let (prefix, base) = match unit.strip_suffix("ib") {
+        Some(prefix) => (prefix, 1024f64),
+        None => match unit.strip_suffix('b') {
+            Some(prefix) if !prefix.is_empty() => (prefix, 1000f64),
+            Some(prefix) => (prefix, 1f64),
+            None => (unit.as_str(), 1024f64),
+        },
+    };
+    let exponent = match prefix {
+        "" => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        _ => return Err(format!("unknown unit in `{value}`")),
+    };
+    Ok((number * base.powi(exponent)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("10G"), Ok(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("10GiB"), Ok(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("10gib"), Ok(10 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("500MB"), Ok(500_000_000));
+        assert_eq!(parse_size("1.5KiB"), Ok(1536));
+        assert_eq!(parse_size("1234"), Ok(1234));
+        assert_eq!(parse_size("1234B"), Ok(1234));
+        assert_eq!(parse_size(" 2 MiB "), Ok(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn invalid_sizes() {
+        assert!(parse_size("abc").is_err());
+        assert!(parse_size("").is_err());
+        assert!(parse_size("2xb").is_err());
+        assert!(parse_size("10PB").is_err());
+    }
+}
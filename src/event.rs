@@ -0,0 +1,131 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+/// Structured progress events for `--progress json`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    CreatorStarted {
+        id: String,
+        name: String,
+    },
+    CreatorFinished {
+        id: String,
+    },
+    PostQueued {
+        id: String,
+        creator: String,
+        title: String,
+    },
+    PostImported {
+        id: String,
+        creator: String,
+        title: String,
+        files: usize,
+    },
+    PostFailed {
+        id: String,
+        creator: String,
+        reason: String,
+    },
+    FileDownloaded {
+        creator: String,
+        url: String,
+        bytes: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Totals {
+    pub creators: (u64, u64),
+    pub posts: (u64, u64),
+    pub files: (u64, u64),
+}
+
+#[derive(Debug, Serialize)]
+struct Line<'a> {
+    time: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+    totals: Totals,
+}
+
+/// Writes every event as a single JSON line, and broadcasts it to the subscribers
+#[derive(Clone)]
+pub struct EventSink {
+    writer: Option<Arc<Mutex<Box<dyn Write + Send>>>>,
+    broadcast: broadcast::Sender<String>,
+}
+
+impl EventSink {
+    pub fn stdout() -> Self {
+        Self::new(Some(Box::new(io::stdout())))
+    }
+
+    pub fn file(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(Some(Box::new(File::create(path)?))))
+    }
+
+    fn new(writer: Option<Box<dyn Write + Send>>) -> Self {
+        Self {
+            writer: writer.map(|writer| Arc::new(Mutex::new(writer))),
+            broadcast: broadcast::channel(1024).0,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some() || self.broadcast.receiver_count() > 0
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.broadcast.subscribe()
+    }
+
+    pub fn emit(&self, event: &Event, totals: Totals) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let line = Line {
+            time: Utc::now(),
+            event,
+            totals,
+        };
+        let line = serde_json::to_string(&line).expect("Failed to serialize event");
+
+        if let Some(writer) = &self.writer {
+            let mut writer = writer.lock().unwrap();
+            let result = writeln!(writer, "{line}").and_then(|_| writer.flush());
+            if let Err(e) = result {
+                error!("Failed to write progress event: {e}");
+            }
+        }
+
+        // having no subscriber is not an error
+        self.broadcast.send(line).ok();
+    }
+}
+
+impl Default for EventSink {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl std::fmt::Debug for EventSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventSink")
+            .field("writer", &self.writer.is_some())
+            .field("subscribers", &self.broadcast.receiver_count())
+            .finish()
+    }
+}
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+use log::{info, warn};
+use post_archiver::{manager::PostArchiverManager, Author, FileMeta};
+use post_archiver_utils::Result;
+
+use super::{open, sanitize, select, zip::ZipWriter, ExportPost};
+use crate::{config::command::ExportCbz, markdown::escape, post::IMAGE_ORDER};
+
+/// The images of the selected posts as one book, or a book for every post with
+/// `--split`. Pages follow the gallery order of every post, recorded on the files.
+pub fn export(args: &ExportCbz) -> Result<()> {
+    let manager = open(&args.archive)?;
+    let (author, posts) = select(&manager, &args.select)?;
+    let posts = posts
+        .iter()
+        .filter(|post| post.content_files().any(is_image))
+        .collect::<Vec<_>>();
+    if posts.is_empty() {
+        warn!("No image in the selected posts");
+        return Ok(());
+    }
+
+    let series = args
+        .select
+        .collection
+        .clone()
+        .unwrap_or_else(|| author.name.clone());
+    let book = Book {
+        manager: &manager,
+        archive: &args.archive.archive,
+        author: &author,
+        series: &series,
+    };
+
+    if args.split {
+        fs::create_dir_all(&args.dest)?;
+        let mut names = HashSet::new();
+        for (i, post) in posts.iter().enumerate() {
+            let name = format!(
+                "{} {}",
+                post.post.published.format("%Y-%m-%d"),
+                sanitize(&post.post.title)
+            );
+            let mut name = name.trim_end().to_string();
+            if !names.insert(name.clone()) {
+                // same day and title, told apart by the id
+                name = format!("{name} ({})", post.post.id.raw());
+                names.insert(name.clone());
+            }
+            let path = args.dest.join(format!("{name}.cbz"));
+            book.write(&path, &post.post.title, Some(i + 1), &[post])?;
+        }
+        info!("{} books written to {}", posts.len(), args.dest.display());
+    } else {
+        info!("Binding {} posts into {series}", posts.len());
+        book.write(&args.dest, &series, None, &posts)?;
+        info!("Book written to {}", args.dest.display());
+    }
+    Ok(())
+}
+
+/// The images of the post in the gallery order, the others after them in the order
+/// of the content, like the posts archived before the order was recorded
+fn gallery(post: &ExportPost) -> Vec<&FileMeta> {
+    let mut images = post
+        .content_files()
+        .filter(|file| is_image(file))
+        .collect::<Vec<_>>();
+    images.sort_by_key(|file| {
+        file.extra
+            .get(IMAGE_ORDER)
+            .and_then(|position| position.as_u64())
+            .unwrap_or(u64::MAX)
+    });
+    images
+}
+
+struct Book<'a> {
+    manager: &'a PostArchiverManager,
+    archive: &'a Path,
+    author: &'a Author,
+    series: &'a str,
+}
+
+impl Book<'_> {
+    fn write(
+        &self,
+        path: &Path,
+        title: &str,
+        number: Option<usize>,
+        posts: &[&ExportPost],
+    ) -> Result<()> {
+        let mut zip = ZipWriter::new(BufWriter::new(File::create(path)?));
+
+        let mut pages = 0;
+        for file in posts.iter().flat_map(|post| gallery(post)) {
+            match fs::read(self.archive.join(file.path())) {
+                Ok(data) => {
+                    pages += 1;
+                    let extension = file.filename.rsplit_once('.').map_or("", |(_, ext)| ext);
+                    zip.add(&format!("{pages:04}.{extension}"), &data)?;
+                }
+                Err(e) => warn!("Failed to read image {}: {e}", file.path().display()),
+            }
+        }
+
+        zip.add(
+            "ComicInfo.xml",
+            self.comic_info(title, number, pages, posts)?.as_bytes(),
+        )?;
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Metadata read by the comic readers, in the ComicRack schema
+    fn comic_info(
+        &self,
+        title: &str,
+        number: Option<usize>,
+        pages: usize,
+        posts: &[&ExportPost],
+    ) -> Result<String> {
+        let platform = self.manager.find_platform("patreon")?;
+        let mut tags = vec![];
+        for post in posts {
+            let (post_tags, _) = post.tags_and_tiers(platform);
+            tags.extend(post_tags);
+            for id in &post.collections {
+                if let Some(collection) = self.manager.get_collection(*id)? {
+                    tags.push(collection.name);
+                }
+            }
+        }
+        tags.sort();
+        tags.dedup();
+        // the list is comma separated
+        let tags = tags
+            .iter()
+            .map(|tag| tag.replace(',', " "))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let published = posts[0].post.published;
+        let mut xml = String::from(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ComicInfo xmlns:xsd="http://www.w3.org/2001/XMLSchema" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+"#,
+        );
+        writeln!(xml, "  <Title>{}</Title>", escape(title)).unwrap();
+        writeln!(xml, "  <Series>{}</Series>", escape(self.series)).unwrap();
+        if let Some(number) = number {
+            writeln!(xml, "  <Number>{number}</Number>").unwrap();
+        }
+        writeln!(xml, "  <Year>{}</Year>", published.format("%Y")).unwrap();
+        writeln!(xml, "  <Month>{}</Month>", published.format("%-m")).unwrap();
+        writeln!(xml, "  <Day>{}</Day>", published.format("%-d")).unwrap();
+        writeln!(xml, "  <Writer>{}</Writer>", escape(&self.author.name)).unwrap();
+        if !tags.is_empty() {
+            writeln!(xml, "  <Tags>{}</Tags>", escape(&tags)).unwrap();
+        }
+        if let [post] = posts {
+            if let Some(source) = &post.post.source {
+                writeln!(xml, "  <Web>{}</Web>", escape(source)).unwrap();
+            }
+        }
+        writeln!(xml, "  <PageCount>{pages}</PageCount>").unwrap();
+        xml.push_str("</ComicInfo>\n");
+        Ok(xml)
+    }
+}
+
+fn is_image(file: &FileMeta) -> bool {
+    file.mime.starts_with("image/")
+}
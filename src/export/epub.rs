@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    fs::{self, File},
+    io::BufWriter,
+};
+
+use chrono::{SecondsFormat, Utc};
+use log::{info, warn};
+use post_archiver::FileMeta;
+use post_archiver_utils::Result;
+
+use super::{open, select, zip::ZipWriter};
+use crate::{
+    config::command::ExportEpub,
+    markdown::{content_to_html, escape},
+};
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+/// A chapter for every post, with the images of the content embedded
+pub fn export(args: &ExportEpub) -> Result<()> {
+    let manager = open(&args.archive)?;
+    let archive = &args.archive.archive;
+    let (author, posts) = select(&manager, &args.select)?;
+
+    let title = args
+        .title
+        .clone()
+        .or_else(|| args.select.collection.clone())
+        .unwrap_or_else(|| author.name.clone());
+    info!("Binding {} posts into {title}", posts.len());
+
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(&args.dest)?));
+    // must be the first entry, and stored
+    zip.add("mimetype", b"application/epub+zip")?;
+    zip.add("META-INF/container.xml", CONTAINER.as_bytes())?;
+
+    let mut manifest = String::new();
+    let mut spine = String::new();
+    let mut toc = vec![];
+    for (i, post) in posts.iter().enumerate() {
+        // only the images are embedded, the other files are left out
+        let mut images = HashMap::new();
+        for (id, file) in &post.files {
+            if !file.mime.starts_with("image/") {
+                continue;
+            }
+
+            match fs::read(archive.join(file.path())) {
+                Ok(data) => {
+                    let href = image_path(file);
+                    zip.add(&format!("OEBPS/{href}"), &data)?;
+                    writeln!(
+                        manifest,
+                        r#"    <item id="image{}" href="{href}" media-type="{}"/>"#,
+                        id.raw(),
+                        escape(&file.mime)
+                    )
+                    .unwrap();
+                    images.insert(*id, file.clone());
+                }
+                Err(e) => warn!("Failed to read image {}: {e}", file.path().display()),
+            }
+        }
+
+        let content = content_to_html(&post.post.content, &images, |file| {
+            format!("../{}", image_path(file))
+        });
+        let chapter = format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="{language}">
+<head><title>{title}</title></head>
+<body>
+<h1>{title}</h1>
+<p>{date}</p>
+{content}</body>
+</html>
+"#,
+            language = escape(&args.language),
+            title = escape(&post.post.title),
+            date = post.post.published.format("%Y-%m-%d"),
+        );
+
+        let number = i + 1;
+        let href = format!("text/{number:04}.xhtml");
+        zip.add(&format!("OEBPS/{href}"), chapter.as_bytes())?;
+        writeln!(
+            manifest,
+            r#"    <item id="chapter{number}" href="{href}" media-type="application/xhtml+xml"/>"#
+        )
+        .unwrap();
+        writeln!(spine, r#"    <itemref idref="chapter{number}"/>"#).unwrap();
+        toc.push((href, post.post.title.clone()));
+    }
+
+    let mut nav = String::new();
+    let mut points = String::new();
+    for (i, (href, title)) in toc.iter().enumerate() {
+        let title = escape(title);
+        writeln!(nav, r#"      <li><a href="{href}">{title}</a></li>"#).unwrap();
+        writeln!(
+            points,
+            r#"    <navPoint id="point{0}" playOrder="{0}"><navLabel><text>{title}</text></navLabel><content src="{href}"/></navPoint>"#,
+            i + 1
+        )
+        .unwrap();
+    }
+
+    let identifier = format!(
+        "urn:patreon-archive:{}:{}",
+        author.id.raw(),
+        urlencoding::encode(&title)
+    );
+    let book = escape(&title);
+    let language = escape(&args.language);
+
+    zip.add(
+        "OEBPS/nav.xhtml",
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{language}">
+<head><title>{book}</title></head>
+<body>
+  <nav epub:type="toc">
+    <h1>{book}</h1>
+    <ol>
+{nav}    </ol>
+  </nav>
+</body>
+</html>
+"#
+        )
+        .as_bytes(),
+    )?;
+
+    // for the readers of EPUB 2
+    zip.add(
+        "OEBPS/toc.ncx",
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head><meta name="dtb:uid" content="{identifier}"/></head>
+  <docTitle><text>{book}</text></docTitle>
+  <navMap>
+{points}  </navMap>
+</ncx>
+"#,
+            identifier = escape(&identifier),
+        )
+        .as_bytes(),
+    )?;
+
+    zip.add(
+        "OEBPS/content.opf",
+        format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{book}</dc:title>
+    <dc:creator>{creator}</dc:creator>
+    <dc:language>{language}</dc:language>
+    <dc:date>{published}</dc:date>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+    <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+{manifest}  </manifest>
+  <spine toc="ncx">
+{spine}  </spine>
+</package>
+"#,
+            identifier = escape(&identifier),
+            creator = escape(&author.name),
+            published = posts[0].post.published.format("%Y-%m-%d"),
+            modified = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        )
+        .as_bytes(),
+    )?;
+
+    zip.finish()?;
+    info!("Book written to {}", args.dest.display());
+    Ok(())
+}
+
+/// Path of an image in the book, relative to the package
+fn image_path(file: &FileMeta) -> String {
+    let extension = file.filename.rsplit_once('.').map_or("", |(_, ext)| ext);
+    format!("images/{}.{extension}", file.id.raw())
+}
@@ -0,0 +1,319 @@
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+use log::{info, warn};
+use post_archiver::{query::Query, Author, AuthorId, Collection, CollectionId, Comment, FileMeta};
+use post_archiver_utils::Result;
+
+use super::{link_file, open, ExportPost};
+use crate::{
+    config::command::ExportHtml,
+    feed::encode_path,
+    markdown::{content_to_html, escape},
+};
+
+const STYLE: &str = r#"body { margin: 0; font-family: system-ui, sans-serif; line-height: 1.5; color: #222; background: #fafafa; }
+nav { padding: .75rem 1.5rem; background: #052d49; }
+nav a { color: #fff; font-weight: bold; text-decoration: none; }
+main { max-width: 960px; margin: 0 auto; padding: 1rem 1.5rem; }
+img, video { max-width: 100%; height: auto; }
+audio { width: 100%; }
+.grid { display: grid; grid-template-columns: repeat(auto-fill, minmax(200px, 1fr)); gap: 1rem; padding: 0; list-style: none; }
+.grid li { background: #fff; border-radius: 6px; overflow: hidden; box-shadow: 0 1px 3px #0002; }
+.grid img { display: block; width: 100%; aspect-ratio: 16 / 9; object-fit: cover; }
+.grid a { display: block; padding: .5rem; color: inherit; text-decoration: none; }
+.meta, .date { color: #666; font-size: .9rem; }
+.tags a { display: inline-block; margin: 0 .25rem .25rem 0; padding: 0 .5rem; border-radius: 1rem; background: #e4e9ee; color: inherit; text-decoration: none; }
+.pages { display: flex; gap: 1rem; justify-content: center; }
+table { border-collapse: collapse; }
+th, td { padding: .25rem .75rem; border: 1px solid #ccc; }
+blockquote { margin-left: 0; padding-left: 1rem; border-left: 3px solid #ccc; }
+.comments ul { padding-left: 1.5rem; list-style: none; }
+.comments li { margin: .5rem 0; }
+"#;
+
+/// Write the site with an index of the creators, the post lists of every creator and
+/// collection, and a page for every post. The links are relative, so the folder can
+/// be browsed from a file share.
+pub fn export(args: &ExportHtml) -> Result<()> {
+    let manager = open(&args.archive)?;
+    let archive = &args.archive.archive;
+    let site = &args.dest;
+    let page_size = args.page_size.max(1);
+    fs::create_dir_all(site)?;
+
+    let posts = ExportPost::load_all(&manager)?;
+    let mut authors: Vec<Author> = manager.authors().query()?;
+    authors.sort_by_key(|author| author.name.to_lowercase());
+    let collections: Vec<Collection> = manager.collections().query()?;
+
+    let site_info = Site {
+        authors: authors
+            .iter()
+            .map(|author| (author.id, author.name.clone()))
+            .collect(),
+        collections: collections
+            .iter()
+            .map(|collection| (collection.id, collection.name.clone()))
+            .collect(),
+    };
+
+    info!("Linking files");
+    for file in posts.iter().flat_map(|post| post.files.values()) {
+        let dest = site.join("files").join(file.path());
+        if let Err(e) = link_file(archive, file, &dest) {
+            warn!("Failed to link file {}: {e}", file.path().display());
+        }
+    }
+
+    info!("Writing {} posts", posts.len());
+    for post in &posts {
+        let path = site
+            .join("posts")
+            .join(format!("{}.html", post.post.id.raw()));
+        write(&path, &site_info.post_page(post))?;
+    }
+
+    let mut index = String::from("<h1>Creators</h1>\n<ul class=\"grid\">\n");
+    for author in &authors {
+        let author_posts = posts
+            .iter()
+            .filter(|post| post.authors.contains(&author.id))
+            .collect::<Vec<_>>();
+        if author_posts.is_empty() {
+            continue;
+        }
+
+        let mut header = format!("<h1>{}</h1>\n", escape(&author.name));
+        let mut tags = author_posts
+            .iter()
+            .flat_map(|post| post.collections.iter().copied())
+            .collect::<Vec<_>>();
+        tags.sort_by_key(|id| site_info.collections.get(id).cloned());
+        tags.dedup();
+        if !tags.is_empty() {
+            header.push_str("<p class=\"tags\">");
+            for id in tags {
+                header.push_str(&site_info.tag_link("../../", id));
+            }
+            header.push_str("</p>\n");
+        }
+
+        let folder = site.join("creators").join(author.id.raw().to_string());
+        site_info.write_list(&folder, &author.name, &header, &author_posts, page_size)?;
+
+        let thumb = author
+            .thumb
+            .and_then(|thumb| manager.get_file_meta(thumb).ok().flatten());
+        writeln!(
+            index,
+            r#"<li><a href="creators/{}/index.html">{}<strong>{}</strong><br><span class="date">{} posts</span></a></li>"#,
+            author.id.raw(),
+            thumb.map(|thumb| image("", &thumb)).unwrap_or_default(),
+            escape(&author.name),
+            author_posts.len()
+        )
+        .unwrap();
+    }
+    index.push_str("</ul>\n");
+    write(&site.join("index.html"), &page("Creators", "", &index))?;
+
+    for collection in &collections {
+        let collection_posts = posts
+            .iter()
+            .filter(|post| post.collections.contains(&collection.id))
+            .collect::<Vec<_>>();
+        if collection_posts.is_empty() {
+            continue;
+        }
+
+        let header = format!("<h1>{}</h1>\n", escape(&collection.name));
+        let folder = site.join("tags").join(collection.id.raw().to_string());
+        site_info.write_list(
+            &folder,
+            &collection.name,
+            &header,
+            &collection_posts,
+            page_size,
+        )?;
+    }
+
+    write(&site.join("style.css"), STYLE)?;
+    info!("Site written to {}", site.join("index.html").display());
+    Ok(())
+}
+
+struct Site {
+    authors: HashMap<AuthorId, String>,
+    collections: HashMap<CollectionId, String>,
+}
+
+impl Site {
+    /// Paginated list, `index.html` then `2.html`, `3.html`...
+    fn write_list(
+        &self,
+        folder: &Path,
+        title: &str,
+        header: &str,
+        posts: &[&ExportPost],
+        page_size: usize,
+    ) -> Result<()> {
+        let root = "../../";
+        let pages = posts.len().div_ceil(page_size);
+        let file_name = |page: usize| match page {
+            1 => "index.html".to_string(),
+            page => format!("{page}.html"),
+        };
+
+        for (i, chunk) in posts.chunks(page_size).enumerate() {
+            let number = i + 1;
+            let mut body = header.to_string();
+            body.push_str("<ul class=\"grid\">\n");
+            for post in chunk {
+                writeln!(
+                    body,
+                    r#"<li><a href="{root}posts/{}.html">{}<strong>{}</strong><br><span class="date">{}</span></a></li>"#,
+                    post.post.id.raw(),
+                    post.thumb().map(|thumb| image(root, thumb)).unwrap_or_default(),
+                    escape(&post.post.title),
+                    post.post.published.format("%Y-%m-%d")
+                )
+                .unwrap();
+            }
+            body.push_str("</ul>\n");
+
+            if pages > 1 {
+                body.push_str("<p class=\"pages\">");
+                if number > 1 {
+                    let href = file_name(number - 1);
+                    write!(body, r#"<a href="{href}">Previous</a>"#).unwrap();
+                }
+                write!(body, "<span>{number} / {pages}</span>").unwrap();
+                if number < pages {
+                    let href = file_name(number + 1);
+                    write!(body, r#"<a href="{href}">Next</a>"#).unwrap();
+                }
+                body.push_str("</p>\n");
+            }
+
+            write(&folder.join(file_name(number)), &page(title, root, &body))?;
+        }
+        Ok(())
+    }
+
+    fn post_page(&self, post: &ExportPost) -> String {
+        let root = "../";
+        let mut body = format!("<article>\n<h1>{}</h1>\n", escape(&post.post.title));
+
+        let authors = post
+            .authors
+            .iter()
+            .filter_map(|id| {
+                let name = self.authors.get(id)?;
+                Some(format!(
+                    r#"<a href="{root}creators/{}/index.html">{}</a>"#,
+                    id.raw(),
+                    escape(name)
+                ))
+            })
+            .collect::<Vec<_>>();
+        write!(
+            body,
+            "<p class=\"meta\">{} · {}",
+            authors.join(", "),
+            post.post.published.format("%Y-%m-%d %H:%M")
+        )
+        .unwrap();
+        if let Some(source) = &post.post.source {
+            write!(body, r#" · <a href="{}">Patreon</a>"#, escape(source)).unwrap();
+        }
+        body.push_str("</p>\n");
+
+        if !post.collections.is_empty() {
+            body.push_str("<p class=\"tags\">");
+            for id in &post.collections {
+                body.push_str(&self.tag_link(root, *id));
+            }
+            body.push_str("</p>\n");
+        }
+
+        body.push_str(&content_to_html(&post.post.content, &post.files, |file| {
+            file_href(root, file)
+        }));
+
+        if !post.post.comments.is_empty() {
+            body.push_str("<section class=\"comments\">\n<h2>Comments</h2>\n");
+            comments(&mut body, &post.post.comments);
+            body.push_str("</section>\n");
+        }
+        body.push_str("</article>\n");
+
+        page(&post.post.title, root, &body)
+    }
+
+    fn tag_link(&self, root: &str, id: CollectionId) -> String {
+        let name = self.collections.get(&id).map_or("", String::as_str);
+        format!(
+            r#"<a href="{root}tags/{}/index.html">{}</a>"#,
+            id.raw(),
+            escape(name)
+        )
+    }
+}
+
+fn comments(html: &mut String, comments: &[Comment]) {
+    html.push_str("<ul>\n");
+    for comment in comments {
+        write!(
+            html,
+            "<li><strong>{}</strong><br>{}",
+            escape(&comment.user),
+            escape(&comment.text).replace('\n', "<br>")
+        )
+        .unwrap();
+        if !comment.replies.is_empty() {
+            self::comments(html, &comment.replies);
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+}
+
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<link rel="stylesheet" href="{root}style.css">
+</head>
+<body>
+<nav><a href="{root}index.html">Patreon Archive</a></nav>
+<main>
+{body}</main>
+</body>
+</html>
+"#,
+        title = escape(title)
+    )
+}
+
+fn file_href(root: &str, file: &FileMeta) -> String {
+    format!("{root}files/{}", encode_path(&file.path()))
+}
+
+fn image(root: &str, file: &FileMeta) -> String {
+    format!(
+        r#"<img src="{}" alt="" loading="lazy">"#,
+        escape(&file_href(root, file))
+    )
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    fs,
+};
+
+use chrono::SecondsFormat;
+use log::{info, warn};
+use post_archiver::{query::Query, Content};
+use post_archiver_utils::Result;
+
+use super::{link_file, open, patreon_id, sanitize, ExportPost};
+use crate::config::command::ExportMarkdown;
+
+/// Write every post to `<creator>/<YYYY-MM-DD title>/index.md`, next to its files
+pub fn export(args: &ExportMarkdown) -> Result<()> {
+    let manager = open(&args.archive)?;
+    let archive = &args.archive.archive;
+    let platform = manager.find_platform("patreon")?;
+
+    let posts = ExportPost::load_all(&manager)?;
+    let authors = manager
+        .authors()
+        .query()?
+        .into_iter()
+        .map(|author| (author.id, author.name))
+        .collect::<HashMap<_, _>>();
+    let collections = manager
+        .collections()
+        .query()?
+        .into_iter()
+        .map(|collection| (collection.id, collection.name))
+        .collect::<HashMap<_, _>>();
+
+    info!("Writing {} posts", posts.len());
+    let mut folders = HashSet::new();
+    for post in &posts {
+        let creators = post
+            .authors
+            .iter()
+            .filter_map(|id| authors.get(id).cloned())
+            .collect::<Vec<_>>();
+        let creator = creators.first().map_or("Unknown", String::as_str);
+
+        let name = format!(
+            "{} {}",
+            post.post.published.format("%Y-%m-%d"),
+            sanitize(&post.post.title)
+        );
+        let mut folder = args.dest.join(sanitize(creator)).join(name.trim_end());
+        if !folders.insert(folder.clone()) {
+            // same day and title, told apart by the id
+            folder = folder.with_file_name(format!("{name} ({})", post.post.id.raw()));
+            folders.insert(folder.clone());
+        }
+        fs::create_dir_all(&folder)?;
+
+        for file in post.files.values() {
+            if let Err(e) = link_file(archive, file, &folder.join(&file.filename)) {
+                warn!("Failed to link file {}: {e}", file.path().display());
+            }
+        }
+
+        let (mut tags, tiers) = post.tags_and_tiers(platform);
+        tags.extend(
+            post.collections
+                .iter()
+                .filter_map(|id| collections.get(id).cloned()),
+        );
+
+        let mut note = String::from("---\n");
+        let source = post.post.source.as_deref();
+        if let Some(id) = source.and_then(patreon_id) {
+            writeln!(note, "id: {}", yaml(id)).unwrap();
+        }
+        if let Some(source) = source {
+            writeln!(note, "url: {}", yaml(source)).unwrap();
+        }
+        writeln!(note, "title: {}", yaml(&post.post.title)).unwrap();
+        writeln!(note, "creator: {}", yaml(creator)).unwrap();
+        writeln!(
+            note,
+            "published: {}",
+            post.post
+                .published
+                .to_rfc3339_opts(SecondsFormat::Secs, true)
+        )
+        .unwrap();
+        writeln!(note, "tags: {}", serde_json::to_string(&tags).unwrap()).unwrap();
+        writeln!(note, "tiers: {}", serde_json::to_string(&tiers).unwrap()).unwrap();
+        note.push_str("---\n\n");
+
+        for content in &post.post.content {
+            match content {
+                Content::Text(text) => {
+                    // the importer joins the lines with `<br>`
+                    note.push_str(&text.replace("<br>", "\n"));
+                    note.push_str("\n\n");
+                }
+                Content::File(id) => {
+                    let Some(file) = post.files.get(id) else {
+                        continue;
+                    };
+                    let href = urlencoding::encode(&file.filename);
+                    let embed = if file.mime.starts_with("image/") {
+                        "!"
+                    } else {
+                        ""
+                    };
+                    writeln!(note, "{embed}[{}]({href})\n", file.filename).unwrap();
+                }
+            }
+        }
+
+        fs::write(folder.join("index.md"), note.trim_end().to_string() + "\n")?;
+    }
+
+    info!("Notes written to {}", args.dest.display());
+    Ok(())
+}
+
+/// A double quoted YAML string, which is also a JSON string
+fn yaml(value: &str) -> String {
+    serde_json::to_string(value).unwrap()
+}
@@ -0,0 +1,226 @@
+pub mod cbz;
+pub mod epub;
+pub mod html;
+pub mod markdown;
+mod zip;
+
+use std::{collections::HashMap, io, path::Path};
+
+use log::info;
+use post_archiver::{
+    manager::{PostArchiverConnection, PostArchiverManager},
+    query::{post::PostSort, Query, SortDir, Sortable},
+    Author, AuthorId, CollectionId, Content, FileMeta, FileMetaId, PlatformId, Post, Tag,
+};
+use post_archiver_utils::Result;
+
+use crate::{
+    config::command::{ArchiveArgs, SelectArgs},
+    fs::link_or_copy,
+    post::TIER_TAG,
+};
+
+/// Open an existing archive, exports never create one
+pub fn open(args: &ArchiveArgs) -> Result<PostArchiverManager> {
+    info!("Opening archive {}", args.archive.display());
+    PostArchiverManager::open(&args.archive)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No archive found in {}", args.archive.display()),
+        )
+        .into()
+    })
+}
+
+/// A post with everything needed to render it
+#[derive(Debug, Clone)]
+pub struct ExportPost {
+    pub post: Post,
+    pub files: HashMap<FileMetaId, FileMeta>,
+    pub authors: Vec<AuthorId>,
+    pub collections: Vec<CollectionId>,
+    pub tags: Vec<Tag>,
+}
+
+impl ExportPost {
+    pub fn load(
+        manager: &PostArchiverManager<impl PostArchiverConnection>,
+        post: Post,
+    ) -> Result<Self> {
+        let binded = manager.bind(post.id);
+
+        let mut files = HashMap::new();
+        for id in binded.list_file_metas()? {
+            if let Some(meta) = manager.get_file_meta(id)? {
+                files.insert(id, meta);
+            }
+        }
+
+        let mut tags = vec![];
+        for id in binded.list_tags()? {
+            if let Some(tag) = manager.get_tag(id)? {
+                tags.push(tag);
+            }
+        }
+
+        Ok(Self {
+            authors: binded.list_authors()?,
+            tags,
+            collections: binded.list_collections()?,
+            files,
+            post,
+        })
+    }
+
+    /// Every post of the archive, the newest first
+    pub fn load_all(
+        manager: &PostArchiverManager<impl PostArchiverConnection>,
+    ) -> Result<Vec<Self>> {
+        manager
+            .posts()
+            .sort(PostSort::Published, SortDir::Desc)
+            .query()?
+            .into_iter()
+            .map(|post| Self::load(manager, post))
+            .collect()
+    }
+
+    pub fn thumb(&self) -> Option<&FileMeta> {
+        self.post.thumb.and_then(|thumb| self.files.get(&thumb))
+    }
+
+    /// Files of the post, in the order of the content
+    pub fn content_files(&self) -> impl Iterator<Item = &FileMeta> {
+        self.post
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                Content::File(id) => self.files.get(id),
+                Content::Text(_) => None,
+            })
+    }
+
+    /// The names of the tags, and the tiers recorded as tags of the platform
+    pub fn tags_and_tiers(&self, platform: Option<PlatformId>) -> (Vec<String>, Vec<String>) {
+        let mut tags = vec![];
+        let mut tiers = vec![];
+        for tag in &self.tags {
+            match tag.name.strip_prefix(TIER_TAG) {
+                Some(tier) if tag.platform.is_some() && tag.platform == platform => {
+                    tiers.push(tier.to_string())
+                }
+                _ => tags.push(tag.name.clone()),
+            }
+        }
+        (tags, tiers)
+    }
+}
+
+/// Find the creator by campaign id, or by name
+pub fn find_author(
+    manager: &PostArchiverManager<impl PostArchiverConnection>,
+    creator: &str,
+) -> Result<Author> {
+    let by_alias = match manager.find_platform("patreon")? {
+        Some(platform) => manager.find_author_by_alias(creator, platform)?,
+        None => None,
+    };
+    let author = match by_alias {
+        Some(id) => manager.get_author(id)?,
+        None => manager
+            .authors()
+            .query()?
+            .into_iter()
+            .find(|author| author.name.eq_ignore_ascii_case(creator)),
+    };
+
+    author.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No creator found for {creator}"),
+        )
+        .into()
+    })
+}
+
+/// The selected posts, the oldest first
+pub fn select(
+    manager: &PostArchiverManager<impl PostArchiverConnection>,
+    args: &SelectArgs,
+) -> Result<(Author, Vec<ExportPost>)> {
+    let author = find_author(manager, &args.creator)?;
+
+    let mut query = manager.posts();
+    query.authors.insert(author.id);
+    let posts = query.sort(PostSort::Published, SortDir::Asc).query()?;
+
+    let mut selected = vec![];
+    for post in posts {
+        let source = post.source.as_deref().unwrap_or_default();
+        if !args.post.is_empty()
+            && !args
+                .post
+                .iter()
+                .any(|post| post == source || patreon_id(source) == Some(post))
+        {
+            continue;
+        }
+
+        let day = post.published.date_naive();
+        if args.since.is_some_and(|since| day < since)
+            || args.until.is_some_and(|until| day > until)
+        {
+            continue;
+        }
+
+        let post = ExportPost::load(manager, post)?;
+        if let Some(name) = &args.collection {
+            let mut names = post
+                .collections
+                .iter()
+                .filter_map(|id| manager.get_collection(*id).ok().flatten());
+            if !names.any(|collection| collection.name.eq_ignore_ascii_case(name)) {
+                continue;
+            }
+        }
+        selected.push(post);
+    }
+
+    if selected.is_empty() {
+        Err(io::Error::new(io::ErrorKind::NotFound, "No post matched").into())
+    } else {
+        Ok((author, selected))
+    }
+}
+
+/// Hard link the archived file into the export, or copy it across filesystems
+pub fn link_file(archive: &Path, meta: &FileMeta, dest: &Path) -> io::Result<()> {
+    let src = archive.join(meta.path());
+    if dest
+        .metadata()
+        .is_ok_and(|dest| src.metadata().is_ok_and(|src| src.len() == dest.len()))
+    {
+        return Ok(());
+    }
+    link_or_copy(&src, dest)
+}
+
+/// The id of the post on Patreon, the end of its url
+pub fn patreon_id(source: &str) -> Option<&str> {
+    let id = source.rsplit(['/', '-']).next()?;
+    (!id.is_empty() && id.chars().all(|c| c.is_ascii_digit())).then_some(id)
+}
+
+/// Name of a file or folder, without the characters refused by the filesystems
+pub fn sanitize(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect::<String>();
+    let name = name.trim().trim_end_matches('.').trim_end();
+    name.chars().take(100).collect()
+}
@@ -0,0 +1,31 @@
+//! ZIP archives with the entries stored without compression
+//!
+//! This is all EPUB and CBZ need, and the media inside are compressed already. ZIP64
+//! is used once an archive outgrows the 4 GiB or 65535 entries of the classic format.
+
+use std::io::{self, Seek, Write};
+
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime};
+
+pub struct ZipWriter<W: Write + Seek>(zip::ZipWriter<W>);
+
+impl<W: Write + Seek> ZipWriter<W> {
+    pub fn new(out: W) -> Self {
+        Self(zip::ZipWriter::new(out))
+    }
+
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        // a fixed modification time, 1980-01-01 00:00, for reproducible archives
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .last_modified_time(DateTime::default())
+            .large_file(data.len() as u64 >= u32::MAX as u64);
+        self.0.start_file(name, options)?;
+        self.0.write_all(data)
+    }
+
+    /// Write the central directory
+    pub fn finish(self) -> io::Result<W> {
+        Ok(self.0.finish()?)
+    }
+}
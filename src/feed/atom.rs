@@ -0,0 +1,77 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use super::{CreatorFeed, FeedPost, Links};
+use crate::markdown::escape;
+
+pub fn render(feed: &CreatorFeed, links: &Links) -> String {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+    writeln!(xml, r#"<feed xmlns="http://www.w3.org/2005/Atom">"#).unwrap();
+    writeln!(xml, "  <id>urn:patreon:campaign:{}</id>", escape(&feed.id)).unwrap();
+    writeln!(xml, "  <title>{}</title>", escape(&feed.name)).unwrap();
+    writeln!(xml, "  <updated>{}</updated>", date(&feed.updated)).unwrap();
+    writeln!(
+        xml,
+        "  <author><name>{}</name></author>",
+        escape(&feed.name)
+    )
+    .unwrap();
+    if let Some(link) = &feed.link {
+        writeln!(xml, r#"  <link rel="alternate" href="{}"/>"#, escape(link)).unwrap();
+    }
+
+    for entry in &feed.posts {
+        render_entry(&mut xml, entry, links);
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn render_entry(xml: &mut String, entry: &FeedPost, links: &Links) {
+    let post = &entry.post;
+    let id = match &post.source {
+        Some(source) => escape(source),
+        None => format!("urn:patreon-archive:post:{}", post.id.raw()),
+    };
+
+    writeln!(xml, "  <entry>").unwrap();
+    writeln!(xml, "    <id>{id}</id>").unwrap();
+    writeln!(xml, "    <title>{}</title>", escape(&post.title)).unwrap();
+    writeln!(xml, "    <published>{}</published>", date(&post.published)).unwrap();
+    writeln!(xml, "    <updated>{}</updated>", date(&post.updated)).unwrap();
+    if let Some(source) = &post.source {
+        writeln!(
+            xml,
+            r#"    <link rel="alternate" href="{}"/>"#,
+            escape(source)
+        )
+        .unwrap();
+    }
+    for file in entry.content_files() {
+        writeln!(
+            xml,
+            r#"    <link rel="enclosure" href="{}" type="{}" length="{}"/>"#,
+            escape(&links.file(file)),
+            escape(&file.mime),
+            links.size(file)
+        )
+        .unwrap();
+    }
+    for category in &entry.categories {
+        writeln!(xml, r#"    <category term="{}"/>"#, escape(category)).unwrap();
+    }
+    writeln!(
+        xml,
+        r#"    <content type="html">{}</content>"#,
+        escape(&entry.content(links))
+    )
+    .unwrap();
+    writeln!(xml, "  </entry>").unwrap();
+}
+
+fn date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
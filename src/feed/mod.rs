@@ -0,0 +1,289 @@
+pub mod atom;
+pub mod podcast;
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use log::{info, trace};
+use post_archiver::{
+    manager::{PostArchiverConnection, PostArchiverManager},
+    query::{post::PostSort, Paginate, Query, SortDir, Sortable},
+    Author, AuthorId, Content, FileMeta, FileMetaId, Post, PostId,
+};
+use post_archiver_utils::Result;
+
+use crate::{markdown::content_to_html, Config};
+
+/// The folder of the feeds, relative to the output
+pub const RELATION_PATH: &str = "feeds";
+/// Only the latest posts are kept in a feed
+const ENTRIES: u64 = 100;
+
+/// A creator and its latest archived posts
+#[derive(Debug, Clone)]
+pub struct CreatorFeed {
+    /// Campaign id, also the name of the feed files
+    pub id: String,
+    pub name: String,
+    pub link: Option<String>,
+    pub thumb: Option<FileMeta>,
+    pub updated: DateTime<Utc>,
+    pub posts: Vec<FeedPost>,
+    /// The latest audio posts, loaded on their own so that the older episodes of a
+    /// creator posting mostly text are kept
+    pub episodes: Vec<FeedPost>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedPost {
+    pub post: Post,
+    pub files: HashMap<FileMetaId, FileMeta>,
+    /// Tags and collections
+    pub categories: Vec<String>,
+}
+
+impl CreatorFeed {
+    fn load(
+        manager: &PostArchiverManager<impl PostArchiverConnection>,
+        author: Author,
+        id: String,
+        link: Option<String>,
+    ) -> Result<Self> {
+        let mut query = manager.posts();
+        query.authors.insert(author.id);
+        let posts = query
+            .sort(PostSort::Published, SortDir::Desc)
+            .pagination(ENTRIES, 0)
+            .query()?;
+
+        let posts = posts
+            .into_iter()
+            .map(|post| FeedPost::load(manager, post))
+            .collect::<Result<Vec<_>>>()?;
+
+        let episodes = Self::load_episodes(manager, author.id)?;
+
+        let updated = posts
+            .iter()
+            .chain(&episodes)
+            .map(|entry| entry.post.updated)
+            .max()
+            .unwrap_or(author.updated);
+
+        let thumb = match author.thumb {
+            Some(thumb) => manager.get_file_meta(thumb)?,
+            None => None,
+        };
+
+        Ok(Self {
+            id,
+            name: author.name,
+            link,
+            thumb,
+            updated,
+            posts,
+            episodes,
+        })
+    }
+
+    fn load_episodes(
+        manager: &PostArchiverManager<impl PostArchiverConnection>,
+        author: AuthorId,
+    ) -> Result<Vec<FeedPost>> {
+        // any post with an audio file, only those starting with one are episodes
+        let mut stmt = manager.conn().prepare(
+            "SELECT posts.id FROM posts
+             JOIN author_posts ON author_posts.post = posts.id
+             WHERE author_posts.author = ?
+             AND EXISTS (
+                 SELECT 1 FROM file_metas
+                 WHERE file_metas.post = posts.id AND file_metas.mime LIKE 'audio/%'
+             )
+             ORDER BY posts.published DESC",
+        )?;
+        let ids = stmt
+            .query_map([author], |row| row.get::<_, PostId>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut episodes = vec![];
+        for id in ids {
+            let Some(post) = manager.get_post(id)? else {
+                continue;
+            };
+            let entry = FeedPost::load(manager, post)?;
+            if entry.audio().is_some() {
+                episodes.push(entry);
+            }
+            if episodes.len() as u64 == ENTRIES {
+                break;
+            }
+        }
+        Ok(episodes)
+    }
+}
+
+impl FeedPost {
+    fn load(
+        manager: &PostArchiverManager<impl PostArchiverConnection>,
+        post: Post,
+    ) -> Result<Self> {
+        let binded = manager.bind(post.id);
+
+        let mut files = HashMap::new();
+        for id in binded.list_file_metas()? {
+            if let Some(meta) = manager.get_file_meta(id)? {
+                files.insert(id, meta);
+            }
+        }
+
+        let mut categories = vec![];
+        for id in binded.list_tags()? {
+            if let Some(tag) = manager.get_tag(id)? {
+                categories.push(tag.name);
+            }
+        }
+        for id in binded.list_collections()? {
+            if let Some(collection) = manager.get_collection(id)? {
+                categories.push(collection.name);
+            }
+        }
+
+        Ok(Self {
+            post,
+            files,
+            categories,
+        })
+    }
+
+    /// Files of the post, in the order of the content
+    pub fn content_files(&self) -> impl Iterator<Item = &FileMeta> {
+        self.post
+            .content
+            .iter()
+            .filter_map(|content| match content {
+                Content::File(id) => self.files.get(id),
+                Content::Text(_) => None,
+            })
+    }
+
+    /// The post as HTML, for the readers
+    pub fn content(&self, links: &Links) -> String {
+        content_to_html(&self.post.content, &self.files, |file| links.file(file))
+    }
+
+    /// The audio of an audio post, always the first file of the content
+    pub fn audio(&self) -> Option<&FileMeta> {
+        self.content_files()
+            .next()
+            .filter(|file| file.mime.starts_with("audio/"))
+    }
+
+    /// The cover saved as `<audio name>.thumb.<ext>`, or the thumbnail of the post
+    pub fn cover(&self) -> Option<&FileMeta> {
+        let audio = self.audio()?;
+        let name = audio
+            .filename
+            .rsplit_once('.')
+            .map_or(audio.filename.as_str(), |(name, _)| name);
+        let prefix = format!("{name}.thumb.");
+
+        self.files
+            .values()
+            .find(|file| file.filename.starts_with(&prefix))
+            .or_else(|| self.post.thumb.and_then(|thumb| self.files.get(&thumb)))
+    }
+}
+
+/// Resolve the archived files for the feed readers
+#[derive(Debug, Clone)]
+pub struct Links {
+    output: PathBuf,
+    base: Option<String>,
+}
+
+impl Links {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            output: config.output().clone(),
+            base: config
+                .feed_base_url()
+                .map(|base| base.trim_end_matches('/').to_string()),
+        }
+    }
+
+    /// Absolute with `--feed-base-url`, otherwise relative to the feed folder
+    pub fn file(&self, meta: &FileMeta) -> String {
+        let path = encode_path(&meta.path());
+
+        match &self.base {
+            Some(base) => format!("{base}/{path}"),
+            None => format!("../{path}"),
+        }
+    }
+
+    pub fn size(&self, meta: &FileMeta) -> u64 {
+        self.output
+            .join(meta.path())
+            .metadata()
+            .map(|metadata| metadata.len())
+            .unwrap_or_default()
+    }
+}
+
+/// Percent-encode every segment of a relative path, to be used in a link
+pub fn encode_path(path: &Path) -> String {
+    path.iter()
+        .map(|segment| urlencoding::encode(&segment.to_string_lossy()).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Regenerate the feeds of every creator in the archive
+pub fn write_feeds(manager: &PostArchiverManager, config: &Config) -> Result<()> {
+    let Some(platform) = manager.find_platform("patreon")? else {
+        return Ok(());
+    };
+
+    let folder = config.output().join(RELATION_PATH);
+    fs::create_dir_all(&folder)?;
+
+    let links = Links::new(config);
+    let mut written = 0;
+    for author in manager.authors().query()? {
+        let aliases = manager.bind(author.id).list_aliases()?;
+        let Some(alias) = aliases.into_iter().find(|alias| alias.platform == platform) else {
+            continue;
+        };
+
+        let feed = CreatorFeed::load(manager, author, alias.source, alias.link)?;
+        if feed.posts.is_empty() {
+            continue;
+        }
+
+        let path = folder.join(format!("{}.atom", feed.id));
+        written += write_if_changed(&path, &atom::render(&feed, &links))? as usize;
+
+        if !feed.episodes.is_empty() {
+            let path = folder.join(format!("{}.podcast.xml", feed.id));
+            written += write_if_changed(&path, &podcast::render(&feed, &links))? as usize;
+        }
+    }
+
+    info!("Feeds updated: {written}");
+    Ok(())
+}
+
+/// Keep the modified time of unchanged feeds, for the readers polling them
+fn write_if_changed(path: &Path, content: &str) -> Result<bool> {
+    if fs::read_to_string(path).is_ok_and(|current| current == content) {
+        trace!("Feed unchanged: {}", path.display());
+        return Ok(false);
+    }
+
+    fs::write(path, content)?;
+    Ok(true)
+}
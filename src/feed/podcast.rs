@@ -0,0 +1,116 @@
+use std::fmt::Write;
+
+use post_archiver::FileMeta;
+use serde_json::Value;
+
+use super::{CreatorFeed, FeedPost, Links};
+use crate::markdown::escape;
+
+/// RSS 2.0 with the iTunes tags, only the audio posts are listed
+pub fn render(feed: &CreatorFeed, links: &Links) -> String {
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="utf-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<rss version="2.0" xmlns:itunes="http://www.itunes.com/dtds/podcast-1.0.dtd">"#
+    )
+    .unwrap();
+    writeln!(xml, "<channel>").unwrap();
+    writeln!(xml, "  <title>{}</title>", escape(&feed.name)).unwrap();
+    if let Some(link) = &feed.link {
+        writeln!(xml, "  <link>{}</link>", escape(link)).unwrap();
+    }
+    writeln!(
+        xml,
+        "  <description>Audio posts of {}</description>",
+        escape(&feed.name)
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        "  <lastBuildDate>{}</lastBuildDate>",
+        feed.updated.to_rfc2822()
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        "  <itunes:author>{}</itunes:author>",
+        escape(&feed.name)
+    )
+    .unwrap();
+    writeln!(xml, "  <itunes:block>Yes</itunes:block>").unwrap();
+
+    let image = feed
+        .thumb
+        .as_ref()
+        .or_else(|| feed.episodes.iter().find_map(FeedPost::cover));
+    if let Some(image) = image {
+        writeln!(
+            xml,
+            r#"  <itunes:image href="{}"/>"#,
+            escape(&links.file(image))
+        )
+        .unwrap();
+    }
+
+    for entry in &feed.episodes {
+        if let Some(audio) = entry.audio() {
+            render_item(&mut xml, entry, audio, links);
+        }
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_item(xml: &mut String, entry: &FeedPost, audio: &FileMeta, links: &Links) {
+    let post = &entry.post;
+    let guid = match &post.source {
+        Some(source) => escape(source),
+        None => format!("urn:patreon-archive:post:{}", post.id.raw()),
+    };
+
+    writeln!(xml, "  <item>").unwrap();
+    writeln!(xml, "    <title>{}</title>", escape(&post.title)).unwrap();
+    writeln!(xml, r#"    <guid isPermaLink="false">{guid}</guid>"#).unwrap();
+    if let Some(source) = &post.source {
+        writeln!(xml, "    <link>{}</link>", escape(source)).unwrap();
+    }
+    writeln!(
+        xml,
+        "    <pubDate>{}</pubDate>",
+        post.published.to_rfc2822()
+    )
+    .unwrap();
+    writeln!(
+        xml,
+        r#"    <enclosure url="{}" length="{}" type="{}"/>"#,
+        escape(&links.file(audio)),
+        links.size(audio),
+        escape(&audio.mime)
+    )
+    .unwrap();
+    if let Some(duration) = audio.extra.get("duration_s").and_then(Value::as_u64) {
+        let (hours, minutes, seconds) = (duration / 3600, duration / 60 % 60, duration % 60);
+        writeln!(
+            xml,
+            "    <itunes:duration>{hours:02}:{minutes:02}:{seconds:02}</itunes:duration>"
+        )
+        .unwrap();
+    }
+    if let Some(cover) = entry.cover() {
+        writeln!(
+            xml,
+            r#"    <itunes:image href="{}"/>"#,
+            escape(&links.file(cover))
+        )
+        .unwrap();
+    }
+    writeln!(
+        xml,
+        "    <description>{}</description>",
+        escape(&entry.content(links))
+    )
+    .unwrap();
+    writeln!(xml, "  </item>").unwrap();
+}
@@ -0,0 +1,36 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use log::debug;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+
+/// Write the value as JSON to a temp file renamed into place, so that an interrupted
+/// write never leaves a truncated file behind
+pub fn atomic_write_json<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let folder = path.parent().unwrap();
+    fs::create_dir_all(folder)?;
+
+    let json = serde_json::to_string(value)?;
+    let mut file = NamedTempFile::new_in(folder)?;
+    file.write_all(json.as_bytes())?;
+    file.as_file().sync_all()?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// Hard link `src` to `dest`, replacing it, or copy it across filesystems
+pub fn link_or_copy(src: &Path, dest: &Path) -> io::Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::remove_file(dest).ok();
+    if let Err(e) = fs::hard_link(src, dest) {
+        debug!("Failed to hard link {}, copying: {e}", src.display());
+        fs::copy(src, dest)?;
+    }
+    Ok(())
+}
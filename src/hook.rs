@@ -0,0 +1,180 @@
+use std::{
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use log::{error, trace, warn};
+use serde::Serialize;
+use tokio::{io::AsyncWriteExt, process::Command, sync::Semaphore, time::timeout};
+
+use crate::patreon::post::Post;
+
+/// A user command run after a post is committed or a file is saved
+///
+/// The payload is written as JSON to the stdin of the command, and the most
+/// useful fields are also exported as `PATREON_*` environment variables.
+/// A failing hook is only logged, the archive is never touched.
+#[derive(Debug, Clone, Default)]
+pub struct Hook(Option<Arc<HookInner>>);
+
+#[derive(Debug)]
+struct HookInner {
+    command: String,
+    permits: Semaphore,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HookPayload {
+    pub event: HookEvent,
+    pub id: String,
+    pub archive_id: u32,
+    pub creator: String,
+    pub creator_id: String,
+    pub title: String,
+    pub url: String,
+    pub published: String,
+    /// Folder of the post in the archive
+    pub directory: PathBuf,
+    /// Every file of the post for `post`, the saved file for `file`
+    pub files: Vec<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HookEvent {
+    Post,
+    File,
+}
+
+impl HookEvent {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Post => "post",
+            Self::File => "file",
+        }
+    }
+}
+
+impl HookPayload {
+    pub fn new(post: &Post) -> Self {
+        Self {
+            event: HookEvent::Post,
+            id: post.id.clone(),
+            archive_id: 0,
+            creator: post.campaign.name.clone(),
+            creator_id: post.campaign.id.clone(),
+            title: post.title.clone(),
+            url: post.url.clone(),
+            published: post.published_at.clone(),
+            directory: PathBuf::new(),
+            files: vec![],
+            file_url: None,
+        }
+    }
+
+    /// The payload of a single saved file of this post
+    pub fn file(&self, path: &Path, url: &str) -> Self {
+        Self {
+            event: HookEvent::File,
+            files: vec![path.to_path_buf()],
+            file_url: Some(url.to_string()),
+            ..self.clone()
+        }
+    }
+
+    fn envs(&self) -> Vec<(&'static str, String)> {
+        let mut envs = vec![
+            ("PATREON_EVENT", self.event.as_str().to_string()),
+            ("PATREON_POST_ID", self.id.clone()),
+            ("PATREON_ARCHIVE_ID", self.archive_id.to_string()),
+            ("PATREON_CREATOR", self.creator.clone()),
+            ("PATREON_CREATOR_ID", self.creator_id.clone()),
+            ("PATREON_TITLE", self.title.clone()),
+            ("PATREON_URL", self.url.clone()),
+            ("PATREON_PUBLISHED", self.published.clone()),
+            ("PATREON_POST_DIR", self.directory.display().to_string()),
+        ];
+        if let (Some(file), Some(url)) = (self.files.first(), &self.file_url) {
+            envs.push(("PATREON_FILE", file.display().to_string()));
+            envs.push(("PATREON_FILE_URL", url.clone()));
+        }
+        envs
+    }
+}
+
+impl Hook {
+    /// Hooks running at the same time, the others wait for a free slot
+    const CONCURRENCY: usize = 4;
+    const TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+    pub fn new(command: Option<&str>) -> Self {
+        Self(command.map(|command| {
+            Arc::new(HookInner {
+                command: command.to_string(),
+                permits: Semaphore::new(Self::CONCURRENCY),
+            })
+        }))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// The returned future owns everything it needs, so it can be spawned
+    pub fn run(&self, payload: HookPayload) -> impl Future<Output = ()> + 'static {
+        let inner = self.0.clone();
+        async move {
+            let Some(inner) = inner else {
+                return;
+            };
+
+            let _permit = inner.permits.acquire().await.unwrap();
+            let event = payload.event.as_str();
+            match timeout(Self::TIMEOUT, run(&inner.command, &payload)).await {
+                Ok(Ok(())) => trace!("{event} hook finished: {}", payload.url),
+                Ok(Err(e)) => error!("{event} hook failed for {}: {e}", payload.url),
+                Err(_) => warn!("{event} hook timed out for {}", payload.url),
+            }
+        }
+    }
+}
+
+async fn run(command: &str, payload: &HookPayload) -> io::Result<()> {
+    let mut child = shell(command)
+        .envs(payload.envs())
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let input = serde_json::to_vec(payload).unwrap();
+    if let Some(mut stdin) = child.stdin.take() {
+        // the command may exit without reading its input
+        stdin.write_all(&input).await.ok();
+    }
+
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(io::Error::other(format!("exited with {status}")));
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("cmd");
+    cmd.arg("/C").arg(command);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command);
+    cmd
+}
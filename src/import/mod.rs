@@ -0,0 +1,133 @@
+mod sidecar;
+
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+};
+
+use chrono::DateTime;
+use log::{debug, error, info, warn};
+use post_archiver::manager::PostArchiverManager;
+use post_archiver_utils::Result;
+
+use crate::{
+    config::command::ImportDir, creator::sync_campaign, fs::link_or_copy, lock::ArchiveLock,
+    post::conversion_post,
+};
+use sidecar::LocalPost;
+
+/// Import the posts found by their sidecars, the files are hard linked into the
+/// archive, or copied across filesystems. The posts are then known to the sync,
+/// which does not download them again.
+pub fn import_dir(args: &ImportDir) -> Result<()> {
+    let archive = &args.archive.archive;
+    fs::create_dir_all(archive)?;
+    let _lock = ArchiveLock::acquire(archive)?;
+    let mut manager = PostArchiverManager::open_or_create(archive)?;
+    let platform = manager.import_platform("patreon".to_string())?;
+
+    info!("Scanning {}", args.source.display());
+    let mut posts = sidecar::scan(&args.source, args.campaign.as_deref())?;
+    posts.sort_by(|a, b| a.post.published_at.cmp(&b.post.published_at));
+    info!("Found {} posts", posts.len());
+
+    let mut authors = HashMap::new();
+    let mut moved = HashSet::new();
+    let (mut imported, mut archived, mut skipped) = (0, 0, 0);
+    'post: for LocalPost { mut post, files } in posts {
+        let Ok(published) = DateTime::parse_from_rfc3339(&post.published_at) else {
+            warn!("Skipping {}, invalid date {}", post.url, post.published_at);
+            skipped += 1;
+            continue;
+        };
+        if manager
+            .find_post_with_updated(&post.url, &published.to_utc())?
+            .is_some()
+        {
+            debug!("Already archived: {}", post.url);
+            archived += 1;
+            continue;
+        }
+
+        // the cover is optional, the other files are not
+        if post
+            .image
+            .as_ref()
+            .is_some_and(|image| !files.contains_key(&image.url))
+        {
+            post.image = None;
+        }
+        let missing = post
+            .files()
+            .into_iter()
+            .filter(|url| !files.contains_key(url))
+            .count();
+        if missing > 0 {
+            warn!(
+                "Skipping {}, {missing} files not found, left for the sync to download",
+                post.url
+            );
+            skipped += 1;
+            continue;
+        }
+
+        let campaign = post.campaign.clone();
+        let author = match authors.entry(campaign.id.clone()) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => match sync_campaign(&manager, platform, &campaign) {
+                Ok(author) => *entry.insert(author),
+                Err(e) => {
+                    error!("Failed to import the creator of {}: {e}", post.url);
+                    skipped += 1;
+                    continue;
+                }
+            },
+        };
+
+        let title = post.title.clone();
+        let source = post.url.clone();
+        let tx = manager.transaction()?;
+        let saved = match tx.import_post(conversion_post(platform, author, post, vec![]), true) {
+            Ok((_, _, _, saved)) => saved,
+            Err(e) => {
+                error!("Failed to import {source}: {e}");
+                skipped += 1;
+                continue;
+            }
+        };
+
+        let mut placed = vec![];
+        for (path, url) in &saved {
+            if let Err(e) = link_or_copy(&files[url], path) {
+                error!("Failed to import {source}: {e}");
+                // the post is rolled back, so are its files
+                for path in placed {
+                    fs::remove_file(path).ok();
+                }
+                if let Some(folder) = path.parent() {
+                    fs::remove_dir(folder).ok();
+                }
+                skipped += 1;
+                continue 'post;
+            }
+            placed.push(path);
+        }
+
+        tx.commit()?;
+        info!("Post imported: {title}");
+        imported += 1;
+        if args.move_files {
+            moved.extend(saved.into_iter().map(|(_, url)| files[&url].clone()));
+        }
+    }
+
+    // removed once committed, a failed post keeps its files
+    for path in moved {
+        if let Err(e) = fs::remove_file(&path) {
+            warn!("Failed to remove {}: {e}", path.display());
+        }
+    }
+
+    info!("Imported {imported} posts, {archived} already archived, {skipped} skipped");
+    Ok(())
+}
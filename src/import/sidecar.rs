@@ -0,0 +1,336 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use jsonapi_deserialize::deserialize_document;
+use log::{debug, warn};
+use post_archiver::importer::{UnsyncContent, UnsyncFileMeta};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    patreon::{
+        post::{ContentUnlockOption, Image, Media, MediaMetadata, Post, PostTag, Reward},
+        Campaign,
+    },
+    post::file::PatreonFileMeta,
+};
+
+/// A post read from the sidecars, with the local files of its urls
+#[derive(Debug)]
+pub struct LocalPost {
+    pub post: Post,
+    pub files: HashMap<String, PathBuf>,
+}
+
+/// Metadata written by the `metadata` post processor of gallery-dl, next to every
+/// file as `<file>.json`. The attributes of the post are flattened into it.
+#[derive(Debug, Clone, Deserialize)]
+struct GalleryDlFile {
+    id: Value,
+    title: String,
+    #[serde(default)]
+    content: Option<String>,
+    published_at: String,
+    url: String,
+    #[serde(default)]
+    post_type: String,
+    #[serde(default)]
+    comment_count: u32,
+    #[serde(default)]
+    image: Option<Value>,
+    #[serde(default)]
+    min_cents_pledged_to_view: Option<u32>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    campaign: Option<GalleryDlCampaign>,
+    #[serde(default)]
+    creator: Option<GalleryDlCreator>,
+    #[serde(default)]
+    num: u32,
+    /// `images`, `attachments`, `content`, `postfile`, or `image` for the cover
+    #[serde(rename = "type", default)]
+    kind: String,
+    filename: String,
+    extension: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GalleryDlCampaign {
+    id: Value,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct GalleryDlCreator {
+    #[serde(default)]
+    full_name: Option<String>,
+    #[serde(default)]
+    vanity: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+}
+
+/// Find the posts of the sidecars under `source`, either raw API responses of a
+/// post or a post list, or the per file metadata of gallery-dl
+pub fn scan(source: &Path, campaign: Option<&str>) -> io::Result<Vec<LocalPost>> {
+    let mut sidecars = vec![];
+    walk(source, &mut sidecars)?;
+
+    let mut posts = vec![];
+    let mut gallery_dl: HashMap<String, Vec<(GalleryDlFile, PathBuf)>> = HashMap::new();
+    for sidecar in sidecars {
+        let json = fs::read_to_string(&sidecar)?;
+        let Ok(value) = serde_json::from_str::<Value>(&json) else {
+            debug!("Skipping {}, not JSON", sidecar.display());
+            continue;
+        };
+
+        if value.get("data").is_some() {
+            let api_posts = if let Ok(document) = deserialize_document::<Post>(&json) {
+                vec![document.data]
+            } else if let Ok(document) = deserialize_document::<Vec<Post>>(&json) {
+                document.data
+            } else {
+                debug!("Skipping {}, not a post", sidecar.display());
+                continue;
+            };
+
+            let dir = sidecar.parent().unwrap_or(Path::new("."));
+            let candidates = files_in(dir)?;
+            for post in api_posts {
+                let files = resolve(&post, &candidates, HashMap::new());
+                posts.push(LocalPost { post, files });
+            }
+            continue;
+        }
+
+        match serde_json::from_value::<GalleryDlFile>(value) {
+            Ok(file) => {
+                let path = sidecar.with_extension("");
+                if !path.is_file() {
+                    warn!("File of the sidecar {} not found", sidecar.display());
+                    continue;
+                }
+                let Some(id) = id(&file.id) else {
+                    continue;
+                };
+                gallery_dl.entry(id).or_default().push((file, path));
+            }
+            Err(e) => debug!("Skipping {}: {e}", sidecar.display()),
+        }
+    }
+
+    for (id, mut files) in gallery_dl {
+        files.sort_by_key(|(file, _)| file.num);
+        posts.extend(gallery_dl_post(id, files, campaign));
+    }
+    Ok(posts)
+}
+
+fn gallery_dl_post(
+    id: String,
+    files: Vec<(GalleryDlFile, PathBuf)>,
+    fallback_campaign: Option<&str>,
+) -> Option<LocalPost> {
+    let first = files[0].0.clone();
+    let creator = first.creator.as_ref();
+    let campaign = Campaign {
+        id: match first
+            .campaign
+            .as_ref()
+            .and_then(|campaign| self::id(&campaign.id))
+        {
+            Some(id) => id,
+            None => match fallback_campaign {
+                Some(id) => id.to_string(),
+                None => {
+                    warn!(
+                        "Skipping {}, the campaign is unknown, set it with `--campaign`",
+                        first.url
+                    );
+                    return None;
+                }
+            },
+        },
+        name: first
+            .campaign
+            .as_ref()
+            .and_then(|campaign| campaign.name.clone())
+            .or_else(|| creator.and_then(|creator| creator.full_name.clone()))
+            .or_else(|| creator.and_then(|creator| creator.vanity.clone()))
+            .unwrap_or_else(|| "Unknown".to_string()),
+        url: first
+            .campaign
+            .as_ref()
+            .and_then(|campaign| campaign.url.clone())
+            .or_else(|| creator.and_then(|creator| creator.url.clone()))
+            .unwrap_or_default(),
+    };
+
+    let mut known = HashMap::new();
+    let mut media = vec![];
+    let mut candidates = vec![];
+    for (file, path) in &files {
+        let name = format!("{}.{}", file.filename, file.extension);
+        candidates.push((name.clone(), path.clone()));
+        if file.kind == "image" {
+            // the cover, matched with `image.url` by name
+            continue;
+        }
+
+        // the local path stands for the url, the file is never downloaded
+        let url = path.to_string_lossy().into_owned();
+        known.insert(url.clone(), path.clone());
+        media.push(Arc::new(Media {
+            id: format!("{id}-{}", file.num),
+            file_name: Some(name),
+            download_url: url,
+            image_urls: None,
+            metadata: MediaMetadata {
+                dimensions: None,
+                duration_s: None,
+                others: HashMap::new(),
+            },
+        }));
+    }
+
+    let content_unlock_options = first
+        .min_cents_pledged_to_view
+        .filter(|cents| *cents > 0)
+        .map(|cents| {
+            Arc::new(ContentUnlockOption {
+                id: String::new(),
+                reward: Arc::new(Reward {
+                    id: String::new(),
+                    patron_amount_cents: cents,
+                    title: None,
+                }),
+            })
+        })
+        .into_iter()
+        .collect();
+
+    let post = Post {
+        id,
+        comment_count: first.comment_count,
+        current_user_can_view: true,
+        campaign: Arc::new(campaign),
+        image: first
+            .image
+            .and_then(|image| serde_json::from_value::<Image>(image).ok()),
+        embed: None,
+        content: first.content,
+        post_metadata: None,
+        post_type: first.post_type,
+        published_at: first.published_at,
+        title: first.title,
+        url: first.url,
+        audio: None,
+        audio_preview: None,
+        media,
+        poll: None,
+        content_unlock_options,
+        user_defined_tags: first
+            .tags
+            .into_iter()
+            .map(|value| {
+                Arc::new(PostTag {
+                    id: format!("user_defined;{value}"),
+                    value,
+                })
+            })
+            .collect(),
+    };
+
+    let files = resolve(&post, &candidates, known);
+    Some(LocalPost { post, files })
+}
+
+/// Match the urls of the post with the local files by their original name. The
+/// downloaders often prefix the names with a number, so a name ending with the
+/// expected one after a separator matches too, as long as only one file does. A file
+/// is matched with one url at most.
+fn resolve(
+    post: &Post,
+    candidates: &[(String, PathBuf)],
+    mut known: HashMap<String, PathBuf>,
+) -> HashMap<String, PathBuf> {
+    let mut expected = post
+        .contents()
+        .into_iter()
+        .filter_map(|content| match content {
+            UnsyncContent::File(file) => Some((file.data, file.filename)),
+            UnsyncContent::Text(_) => None,
+        })
+        .collect::<Vec<_>>();
+    if let Some(image) = &post.image {
+        let thumb = UnsyncFileMeta::from_url(image.url.clone());
+        expected.push((thumb.data, thumb.filename));
+    }
+
+    let mut used = known.values().cloned().collect::<HashSet<_>>();
+    for (url, filename) in expected {
+        if known.contains_key(&url) {
+            continue;
+        }
+
+        let unused = || candidates.iter().filter(|(_, path)| !used.contains(path));
+        let found = unused().find(|(name, _)| *name == filename).or_else(|| {
+            let mut prefixed = unused().filter(|(name, _)| {
+                name.strip_suffix(filename.as_str())
+                    .is_some_and(|prefix| prefix.ends_with(['_', '-', ' ']))
+            });
+            match (prefixed.next(), prefixed.next()) {
+                (Some(found), None) => Some(found),
+                _ => None,
+            }
+        });
+        if let Some((_, path)) = found {
+            used.insert(path.clone());
+            known.insert(url, path.clone());
+        }
+    }
+    known
+}
+
+fn walk(dir: &Path, sidecars: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, sidecars)?;
+        } else if path.extension().is_some_and(|ext| ext == "json") {
+            sidecars.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// The files next to a sidecar, by name
+fn files_in(dir: &Path) -> io::Result<Vec<(String, PathBuf)>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_none_or(|ext| ext != "json") {
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            files.push((name, path));
+        }
+    }
+    Ok(files)
+}
+
+/// The ids are numbers in gallery-dl, and strings in the API
+fn id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,91 @@
+use std::{
+    fs::{File, OpenOptions, TryLockError},
+    io::{self, Read, Seek, Write},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// Exclusive lock on the output folder, held for the whole run.
+///
+/// The OS releases the lock when the process dies, so a lock file which still
+/// has an owner but can be locked again was left behind by a crashed run.
+#[derive(Debug)]
+pub struct ArchiveLock(File);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockOwner {
+    pid: u32,
+    host: String,
+    started: DateTime<Utc>,
+}
+
+impl ArchiveLock {
+    pub const RELATION_PATH: &'static str = "configs/patreon-archive.lock";
+
+    pub fn acquire(output: &Path) -> io::Result<Self> {
+        let path = output.join(Self::RELATION_PATH);
+        std::fs::create_dir_all(path.parent().unwrap())?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let owner = read_owner(&mut file)
+                    .map(|owner| {
+                        format!(
+                            "pid {} on {}, started at {}",
+                            owner.pid, owner.host, owner.started
+                        )
+                    })
+                    .unwrap_or_else(|| "unknown owner".to_string());
+                return Err(io::Error::other(format!(
+                    "Another run is in progress on this output ({owner}), lock file: {}",
+                    path.display()
+                )));
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+
+        if let Some(owner) = read_owner(&mut file) {
+            warn!(
+                "Removing stale lock of pid {} on {}, started at {}",
+                owner.pid, owner.host, owner.started
+            );
+        }
+
+        let owner = LockOwner {
+            pid: std::process::id(),
+            host: gethostname::gethostname().to_string_lossy().into_owned(),
+            started: Utc::now(),
+        };
+        file.set_len(0)?;
+        file.rewind()?;
+        file.write_all(serde_json::to_string(&owner)?.as_bytes())?;
+        file.sync_all()?;
+
+        Ok(Self(file))
+    }
+}
+
+impl Drop for ArchiveLock {
+    fn drop(&mut self) {
+        // keep the file itself, removing it would let a racing run lock a stale inode
+        self.0.set_len(0).ok();
+    }
+}
+
+fn read_owner(file: &mut File) -> Option<LockOwner> {
+    let mut json = String::new();
+    file.rewind().ok()?;
+    file.read_to_string(&mut json).ok()?;
+    serde_json::from_str(&json).ok()
+}
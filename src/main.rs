@@ -1,33 +1,69 @@
 #![allow(clippy::too_many_arguments)]
 
 mod api;
+mod bandwidth;
 mod config;
 mod context;
+mod control;
 mod creator;
+mod dedupe;
+mod disk;
+mod event;
+mod export;
+mod feed;
+mod fs;
+mod hook;
+mod import;
+mod lock;
+mod markdown;
+mod metrics;
 mod post;
+mod report;
+mod shutdown;
+mod watch;
+mod webhook;
 
 mod patreon;
 
-use std::{collections::HashMap, error::Error};
+use std::{collections::HashMap, error::Error, time::Duration};
 
 use api::PatreonClient;
-use config::{Config, ProgressSet};
-use context::Context;
+use config::{command::Command, Config, ProgressSet};
+use context::{CachedCampaign, Context};
+use control::ControlServer;
 use creator::list_members;
-use log::{info, warn};
+use lock::ArchiveLock;
+use log::{error, info, warn};
+use metrics::Metrics;
 use patreon::{comment::Comment, post::Post, Member, User};
 use plyne::define_tasks;
-use post::{file::download_files, list_posts, sync_posts};
+use post::{file::download_files, known::KnownPosts, list_posts, sync_posts};
 use post_archiver::{manager::PostArchiverManager, utils::VERSION};
 use post_archiver_utils::display_metadata;
+use report::Report;
+use shutdown::Shutdown;
 use tempfile::TempPath;
 use tokio::sync::{oneshot, Mutex};
+use watch::{Schedule, SyncQueue};
+use webhook::Webhook;
 
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let config = config::Config::parse();
+    let mut config = config::Config::parse();
     config.init_logger();
 
+    if let Some(command) = config.command() {
+        let result = match command {
+            Command::ExportHtml(args) => export::html::export(args),
+            Command::ExportMarkdown(args) => export::markdown::export(args),
+            Command::ExportEpub(args) => export::epub::export(args),
+            Command::ExportCbz(args) => export::cbz::export(args),
+            Command::ImportDir(args) => import::import_dir(args),
+            Command::Dedupe(args) => dedupe::dedupe(args),
+        };
+        return Ok(result.inspect_err(|e| error!("{e}"))?);
+    }
+
     display_metadata(
         "Patreon Archive",
         &[
@@ -41,11 +77,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
         warn!("Creating output folder");
         std::fs::create_dir_all(config.output())?;
     }
+    let _lock = ArchiveLock::acquire(config.output()).inspect_err(|e| error!("{e}"))?;
+    api::clean_staging(&config.staging());
 
-    let client = PatreonClient::new(&config);
+    let mut shutdown = Shutdown::listen();
+    let metrics = Metrics::default();
+    let mut client = PatreonClient::new(&config, metrics.clone());
 
     info!("Checking User Data");
-    let user = client.get_current_user_id().await?;
+    let mut user = client.get_current_user_id().await?;
     info!("= User ===========================");
     info!("Name: {}", user.full_name);
     info!("Id: {}", user.id);
@@ -56,28 +96,129 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let output = config.output().clone();
     let manager = PostArchiverManager::open_or_create(&output)?;
 
-    let context = context::Context::load(&output);
-    let manager = Mutex::new(manager);
-
-    let progress = ProgressSet::new(&config);
+    let mut context = context::Context::load(&output);
+    let mut manager = Mutex::new(manager);
+
+    let mut schedule = Schedule::new(&config);
+    let mut webhook = Webhook::new(
+        config.webhook(),
+        config.webhook_template().map(|p| p.as_path()),
+    )?;
+    let mut progress = ProgressSet::new(&config, metrics.clone())?;
+    let mut report = Report::new();
+
+    let queue = SyncQueue::default();
+    if let Some(addr) = config.listen() {
+        let server = ControlServer {
+            context: context.clone(),
+            queue: queue.clone(),
+            events: progress.events().clone(),
+            metrics,
+        };
+        server.spawn(addr).await?;
+    }
 
-    let PatreonSystemContext { context, .. } =
-        PatreonSystem::new(manager, config, client, user, context.clone(), progress)
+    loop {
+        schedule.force(queue.take());
+        // in watch mode, a transient error of the archive skips the round only
+        let known = match KnownPosts::load(manager.get_mut()) {
+            Ok(known) => Some(known),
+            Err(e) if config.watch().is_some() => {
+                error!("Failed to read the archived posts, skipping this sync: {e}");
+                schedule.fail();
+                None
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(known) = known {
+            PatreonSystemContext {
+                manager,
+                config,
+                client,
+                user,
+                context,
+                progress_set: progress,
+                report,
+                schedule,
+                webhook,
+                shutdown,
+                ..
+            } = PatreonSystem::new(
+                manager, known, config, client, user, context, progress, report, schedule, webhook,
+                shutdown,
+            )
             .execute()
             .await;
 
-    info!("All done!");
+            report.display();
+            if config.feed() {
+                feed::write_feeds(manager.get_mut(), &config)
+                    .unwrap_or_else(|e| error!("Failed to write feeds: {e}"));
+            }
+            if let Some(path) = config.report() {
+                if let Err(e) = report.save(path) {
+                    error!("Failed to save report: {e}");
+                    if config.watch().is_none() {
+                        return Err(e.into());
+                    }
+                }
+            }
+        }
+
+        if !context.failures.is_empty() {
+            warn!(
+                "{} posts failed, they will be retried on the next run",
+                context.failures.len()
+            );
+        }
+
+        if shutdown.requested() {
+            warn!("Stopped early, progress has been saved");
+            break;
+        }
+
+        if config.watch().is_none() {
+            info!("All done!");
+            break;
+        }
+
+        if let Err(e) = context.save(&output) {
+            error!("Failed to save context: {e}");
+        }
+        let delay = schedule.next_delay();
+        let display_delay = Duration::from_secs(delay.as_secs());
+        info!("Next sync in {}", humantime::format_duration(display_delay));
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {},
+            _ = queue.wait() => {},
+            _ = shutdown.wait() => break,
+        }
+
+        progress.reset(&config);
+        report = Report::new();
+    }
 
-    context.save(&output);
+    context.save(&output)?;
     Ok(())
 }
 
-pub type PostsEvent = (
-    Post,
-    Vec<Comment>,
-    oneshot::Receiver<HashMap<String, TempPath>>,
+pub enum PostsEvent {
+    /// Comments are `None` when they failed to load
+    Post(
+        Box<Post>,
+        Option<Vec<Comment>>,
+        oneshot::Receiver<HashMap<String, TempPath>>,
+    ),
+    /// Every post of the campaign has been queued before this event
+    Checkpoint(String, CachedCampaign),
+}
+/// Urls of a post, with their Patreon media id
+pub type FilesEvent = (
+    String,
+    Vec<(String, Option<String>)>,
+    oneshot::Sender<HashMap<String, TempPath>>,
 );
-pub type FilesEvent = (Vec<String>, oneshot::Sender<HashMap<String, TempPath>>);
 
 pub type Manager = Mutex<PostArchiverManager>;
 
@@ -90,11 +231,16 @@ define_tasks! {
     }
     vars {
         manager: Manager,
+        known: KnownPosts,
         config: Config,
         client: PatreonClient,
         user: User,
         context: Context,
         progress_set: ProgressSet,
+        report: Report,
+        schedule: Schedule,
+        webhook: Webhook,
+        shutdown: Shutdown,
     }
     tasks {
         list_members,
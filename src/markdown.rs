@@ -0,0 +1,138 @@
+//! Markdown to HTML, for the text converted by `htmd` at import
+//!
+//! The void elements are closed, so the output is also valid XHTML.
+
+use std::collections::HashMap;
+
+use post_archiver::{Content, FileMeta, FileMetaId};
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+/// Escape the text to be embedded in HTML or XML
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn to_html(markdown: &str) -> String {
+    // the importer joins the lines with `<br>`
+    let text = markdown.replace("<br>", "\n");
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+
+    // whether the opened links and images are kept, they may be nested
+    let mut kept = vec![];
+    let events = Parser::new_ext(&text, options).filter_map(|event| match event {
+        Event::Start(Tag::Link { ref dest_url, .. } | Tag::Image { ref dest_url, .. }) => {
+            let safe = is_safe_url(dest_url);
+            kept.push(safe);
+            safe.then_some(event)
+        }
+        Event::End(TagEnd::Link | TagEnd::Image) => kept.pop().unwrap_or(true).then_some(event),
+        // the HTML left in the text is shown, never interpreted
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        // every line of a post is its own
+        Event::SoftBreak => Some(Event::HardBreak),
+        event => Some(event),
+    });
+
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+/// Only the web and mail links, and the relative ones, are kept. A `javascript:` link
+/// would run in the feed readers and the exported site.
+fn is_safe_url(url: &str) -> bool {
+    let Some(colon) = url.find(':') else {
+        return true;
+    };
+    let scheme = &url[..colon];
+    // a colon after the path, query or fragment starts does not end a scheme
+    scheme.contains(['/', '?', '#'])
+        || ["http", "https", "mailto"]
+            .iter()
+            .any(|safe| scheme.eq_ignore_ascii_case(safe))
+}
+
+/// Render the content of a post, with the files embedded where the content references them
+pub fn content_to_html(
+    content: &[Content],
+    files: &HashMap<FileMetaId, FileMeta>,
+    href: impl Fn(&FileMeta) -> String,
+) -> String {
+    let mut html = String::new();
+    for content in content {
+        match content {
+            Content::Text(text) => html.push_str(&to_html(text)),
+            Content::File(id) => {
+                if let Some(file) = files.get(id) {
+                    html.push_str(&embed(file, &href(file)));
+                }
+            }
+        }
+    }
+    html
+}
+
+fn embed(file: &FileMeta, href: &str) -> String {
+    let href = escape(href);
+    let name = escape(&file.filename);
+    match file.mime.split('/').next() {
+        Some("image") => format!(r#"<p><img src="{href}" alt="{name}" /></p>"#),
+        Some("video") => format!(r#"<p><video src="{href}" controls></video></p>"#),
+        Some("audio") => format!(r#"<p><audio src="{href}" controls></audio></p>"#),
+        _ => format!(r#"<p><a href="{href}">{name}</a></p>"#),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_urls() {
+        assert!(is_safe_url("https://example.com/a"));
+        assert!(is_safe_url("HTTP://example.com"));
+        assert!(is_safe_url("mailto:someone@example.com"));
+        assert!(is_safe_url("images/cover.png"));
+        assert!(is_safe_url("#comments"));
+        assert!(is_safe_url("a/b:c"));
+        assert!(is_safe_url("?page=2:3"));
+    }
+
+    #[test]
+    fn unsafe_urls() {
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("JaVaScRiPt:alert(1)"));
+        assert!(!is_safe_url("data:text/html;base64,PHNjcmlwdD4="));
+        assert!(!is_safe_url("vbscript:msgbox"));
+    }
+
+    #[test]
+    fn raw_html_is_text() {
+        let html = to_html("<script>alert(1)</script>");
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;"));
+
+        let html = to_html("hello <img src=x onerror=alert(1)> world");
+        assert!(!html.contains("<img"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+    }
+
+    #[test]
+    fn unsafe_links_keep_their_text() {
+        let html = to_html("[click](javascript:alert(1)) [site](https://example.com)");
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("click"));
+        assert!(html.contains(r#"<a href="https://example.com">site</a>"#));
+    }
+}
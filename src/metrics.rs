@@ -0,0 +1,276 @@
+use std::{
+    fmt::Write,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use http::Extensions;
+use log::warn;
+use reqwest::{Request, Response, StatusCode};
+use reqwest_middleware::{Middleware, Next};
+
+use crate::event::Event;
+
+/// Counters exposed in the Prometheus text format on `/metrics`
+#[derive(Debug, Clone, Default)]
+pub struct Metrics(Arc<Inner>);
+
+#[derive(Debug, Default)]
+struct Inner {
+    posts_imported: AtomicU64,
+    posts_failed: AtomicU64,
+    files_downloaded: AtomicU64,
+    bytes_downloaded: AtomicU64,
+    http_errors: DashMap<u16, u64>,
+    retries: AtomicU64,
+    rate_limit_waits: AtomicU64,
+    rate_limit_wait_millis: AtomicU64,
+    queues: [AtomicI64; 3],
+    last_sync: DashMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Pipeline {
+    Campaign = 0,
+    Posts = 1,
+    Files = 2,
+}
+
+impl Pipeline {
+    const ALL: [Self; 3] = [Self::Campaign, Self::Posts, Self::Files];
+
+    const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Campaign => "campaign_pipeline",
+            Self::Posts => "posts_pipeline",
+            Self::Files => "files_pipeline",
+        }
+    }
+}
+
+impl Metrics {
+    pub fn observe(&self, event: &Event) {
+        match event {
+            Event::PostImported { .. } => {
+                self.0.posts_imported.fetch_add(1, Ordering::Relaxed);
+            }
+            Event::PostFailed { .. } => {
+                self.0.posts_failed.fetch_add(1, Ordering::Relaxed);
+            }
+            Event::FileDownloaded { bytes, .. } => {
+                self.0.files_downloaded.fetch_add(1, Ordering::Relaxed);
+                self.0.bytes_downloaded.fetch_add(*bytes, Ordering::Relaxed);
+            }
+            Event::CreatorFinished { id } => {
+                self.0.last_sync.insert(id.clone(), Utc::now().timestamp());
+            }
+            Event::CreatorStarted { .. } | Event::PostQueued { .. } => {}
+        }
+    }
+
+    pub fn http_error(&self, status: u16) {
+        *self.0.http_errors.entry(status).or_default() += 1;
+    }
+
+    pub fn retry(&self) {
+        self.0.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rate_limit_wait(&self, wait: Duration) {
+        self.0.rate_limit_waits.fetch_add(1, Ordering::Relaxed);
+        let millis = wait.as_millis() as u64;
+        self.0
+            .rate_limit_wait_millis
+            .fetch_add(millis, Ordering::Relaxed);
+    }
+
+    /// Track the queue depth, `+n` when sending into the pipeline and `-n` when receiving
+    pub fn queued(&self, pipeline: Pipeline, n: i64) {
+        self.0.queues[pipeline as usize].fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn render(&self) -> String {
+        let inner = &self.0;
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: Vec<(String, String)>| {
+            writeln!(out, "# HELP patreon_archive_{name} {help}").unwrap();
+            writeln!(out, "# TYPE patreon_archive_{name} {kind}").unwrap();
+            for (labels, value) in samples {
+                writeln!(out, "patreon_archive_{name}{labels} {value}").unwrap();
+            }
+        };
+        let single =
+            |value: &AtomicU64| vec![(String::new(), value.load(Ordering::Relaxed).to_string())];
+
+        metric(
+            "posts_imported_total",
+            "counter",
+            "Posts imported into the archive",
+            single(&inner.posts_imported),
+        );
+        metric(
+            "posts_failed_total",
+            "counter",
+            "Posts which failed to import",
+            single(&inner.posts_failed),
+        );
+        metric(
+            "files_downloaded_total",
+            "counter",
+            "Files downloaded",
+            single(&inner.files_downloaded),
+        );
+        metric(
+            "bytes_downloaded_total",
+            "counter",
+            "Bytes of the downloaded files",
+            single(&inner.bytes_downloaded),
+        );
+
+        let mut http_errors = inner
+            .http_errors
+            .iter()
+            .map(|e| (*e.key(), *e.value()))
+            .collect::<Vec<_>>();
+        http_errors.sort();
+        metric(
+            "http_errors_total",
+            "counter",
+            "Unsuccessful HTTP responses by status",
+            http_errors
+                .into_iter()
+                .map(|(status, count)| (format!("{{status=\"{status}\"}}"), count.to_string()))
+                .collect(),
+        );
+        metric(
+            "retries_total",
+            "counter",
+            "Retried requests",
+            single(&inner.retries),
+        );
+        metric(
+            "rate_limit_waits_total",
+            "counter",
+            "Times a request waited because of rate limiting",
+            single(&inner.rate_limit_waits),
+        );
+        let wait_secs = inner.rate_limit_wait_millis.load(Ordering::Relaxed) as f64 / 1000.0;
+        metric(
+            "rate_limit_wait_seconds_total",
+            "counter",
+            "Time spent waiting because of rate limiting",
+            vec![(String::new(), wait_secs.to_string())],
+        );
+        metric(
+            "queue_depth",
+            "gauge",
+            "Items waiting in a pipeline",
+            Pipeline::ALL
+                .iter()
+                .map(|pipeline| {
+                    let depth = inner.queues[*pipeline as usize].load(Ordering::Relaxed);
+                    (
+                        format!("{{pipeline=\"{}\"}}", pipeline.as_str()),
+                        depth.to_string(),
+                    )
+                })
+                .collect(),
+        );
+
+        let mut last_sync = inner
+            .last_sync
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect::<Vec<_>>();
+        last_sync.sort();
+        metric(
+            "last_sync_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful sync of a campaign",
+            last_sync
+                .into_iter()
+                .map(|(campaign, time)| (format!("{{campaign=\"{campaign}\"}}"), time.to_string()))
+                .collect(),
+        );
+
+        out
+    }
+}
+
+/// When the request was sent, set on every request of the clients so that
+/// `MetricsMiddleware` can tell the rate limiter waits and the retries apart
+#[derive(Debug, Clone, Copy)]
+pub struct Attempt {
+    since: Instant,
+    retry: bool,
+    /// Whether the request goes through the rate limiter
+    limited: bool,
+}
+
+impl Attempt {
+    pub fn new(limited: bool) -> Self {
+        Self {
+            since: Instant::now(),
+            retry: false,
+            limited,
+        }
+    }
+}
+
+/// Records every attempt of the requests, placed under the rate limiter and the retry
+/// middlewares of the clients so that their waits and retries are counted
+#[derive(Debug, Clone)]
+pub struct MetricsMiddleware(pub Metrics);
+
+impl MetricsMiddleware {
+    /// Shorter delays are the scheduling of the request rather than a wait
+    const MIN_WAIT: Duration = Duration::from_millis(10);
+}
+
+#[async_trait::async_trait]
+impl Middleware for MetricsMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let attempt = extensions.get::<Attempt>().copied();
+        if let Some(attempt) = attempt {
+            if attempt.retry {
+                self.0.retry();
+            } else if attempt.limited {
+                // the rate limiter only holds the first attempt, the retries wait the backoff
+                let wait = attempt.since.elapsed();
+                if wait >= Self::MIN_WAIT {
+                    self.0.rate_limit_wait(wait);
+                }
+            }
+        }
+
+        let url = req.url().clone();
+        let result = next.run(req, extensions).await;
+        if let Ok(response) = &result {
+            let status = response.status();
+            if !status.is_success() {
+                self.0.http_error(status.as_u16());
+            }
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                warn!("Rate limited by {url}");
+            }
+        }
+
+        if let Some(attempt) = attempt {
+            extensions.insert(Attempt {
+                retry: true,
+                ..attempt
+            });
+        }
+        result
+    }
+}
@@ -53,6 +53,15 @@ impl Post {
                 .iter()
                 .any(|e| e.reward.patron_amount_cents == 0)
     }
+
+    /// Tiers unlocking the post, free posts have none
+    pub fn tiers(&self) -> Vec<String> {
+        self.content_unlock_options
+            .iter()
+            .filter(|option| option.reward.patron_amount_cents > 0)
+            .map(|option| option.reward.name())
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -150,6 +159,22 @@ pub struct ContentUnlockOption {
 pub struct Reward {
     pub id: String,
     pub patron_amount_cents: u32,
+    #[json_api(default)]
+    pub title: Option<String>,
+}
+
+impl Reward {
+    /// The title of the tier, or its price when it has none
+    pub fn name(&self) -> String {
+        match &self.title {
+            Some(title) if !title.is_empty() => title.clone(),
+            _ => format!(
+                "${}.{:02}",
+                self.patron_amount_cents / 100,
+                self.patron_amount_cents % 100
+            ),
+        }
+    }
 }
 
 #[derive(Debug, Clone, JsonApiDeserialize)]
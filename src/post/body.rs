@@ -1,8 +1,14 @@
+use std::collections::HashMap;
+
 use htmd::{Element, HtmlToMarkdown};
 use log::error;
 use post_archiver::importer::{UnsyncContent, UnsyncFileMeta};
+use serde_json::json;
 
-use crate::{patreon::post::Post, post::file::PatreonFileMeta};
+use crate::{
+    patreon::post::Post,
+    post::{file::PatreonFileMeta, IMAGE_ORDER},
+};
 
 impl Post {
     pub fn contents(&self) -> Vec<UnsyncContent<String>> {
@@ -75,7 +81,15 @@ impl Post {
                 );
                 contents.push(UnsyncContent::File(file));
             } else {
-                let file = UnsyncFileMeta::from_media(media);
+                // the gallery order chosen by the creator, kept aside as the content
+                // order is the one of the media
+                let position = self.post_metadata.as_ref().and_then(|metadata| {
+                    metadata.image_order.iter().position(|id| id == &media.id)
+                });
+                let mut file = UnsyncFileMeta::from_media(media);
+                if let Some(position) = position {
+                    file.extra.insert(IMAGE_ORDER.to_string(), json!(position));
+                }
                 contents.push(UnsyncContent::File(file));
             }
         }
@@ -130,6 +144,16 @@ impl Post {
         contents
     }
 
+    /// Patreon media ids by download url
+    pub fn media_ids(&self) -> HashMap<String, String> {
+        self.media
+            .iter()
+            .chain(&self.audio)
+            .chain(&self.audio_preview)
+            .map(|media| (media.download_url.clone(), media.id.clone()))
+            .collect()
+    }
+
     pub fn files(&self) -> Vec<String> {
         self.contents()
             .into_iter()
@@ -1,25 +1,44 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, io, path::Path, sync::Arc};
 
-use futures::future::try_join_all;
-use log::{debug, error};
+use futures::future::join_all;
+use log::{debug, error, trace};
 use mime_guess::MimeGuess;
 use plyne::Output;
 use post_archiver::importer::file_meta::UnsyncFileMeta;
 use serde_json::json;
+use tempfile::TempPath;
 use tokio::{sync::Semaphore, task::JoinSet};
 
-use crate::{api::PatreonClient, config::ProgressSet, patreon::post::Media, Config, FilesEvent};
+use crate::{
+    api::{staging_file, PatreonClient},
+    config::ProgressSet,
+    context::Context,
+    disk::SpaceGuard,
+    event::Event,
+    fs::link_or_copy,
+    metrics::Pipeline,
+    patreon::post::Media,
+    report::Report,
+    shutdown::Shutdown,
+    Config, FilesEvent,
+};
 
 pub async fn download_files(
     mut files_pipeline: Output<FilesEvent>,
     config: &Config,
+    client: &PatreonClient,
+    context: &Context,
     pb: &ProgressSet,
+    report: &Report,
+    shutdown: &Shutdown,
 ) {
     let mut tasks = JoinSet::new();
-    let client = PatreonClient::new(config);
 
-    let semaphore = Arc::new(Semaphore::new(3));
-    while let Some((urls, tx)) = files_pipeline.recv().await {
+    let space = SpaceGuard::new(config);
+    let posts = Arc::new(Semaphore::new(config.post_concurrency()));
+    let downloads = Arc::new(Semaphore::new(config.download_concurrency()));
+    while let Some((campaign_id, urls, tx)) = files_pipeline.recv().await {
+        pb.metrics().queued(Pipeline::Files, -1);
         if urls.is_empty() {
             tx.send(Default::default()).unwrap();
             continue;
@@ -27,20 +46,62 @@ pub async fn download_files(
         pb.files.inc_length(urls.len() as u64);
 
         let client = client.clone();
-        let semaphore = semaphore.clone();
-        let file_pb = pb.files.clone();
+        let context = context.clone();
+        let space = space.clone();
+        let shutdown = shutdown.clone();
+        let output = config.output().clone();
+        let staging = config.staging();
+        let posts = posts.clone();
+        let downloads = downloads.clone();
+        let report = report.clone();
+        let pb = pb.clone();
         tasks.spawn(async move {
-            let _permit = semaphore.acquire().await.unwrap();
-            match try_join_all(urls.into_iter().map(|url| async {
+            let _permit = posts.acquire().await.unwrap();
+            // failed downloads are left out, `sync_posts` records them as missing
+            let files = join_all(urls.into_iter().map(|(url, id)| async {
+                // media archived by another post, or by a previous import of this one
+                let archived = id.and_then(|id| context.archived_media(&id, &output));
+                if let Some(archived) = archived {
+                    match local_copy(&archived, &staging) {
+                        Ok(path) => {
+                            trace!("Reused {} for {url}", archived.display());
+                            pb.files.inc(1);
+                            return Some((url, path));
+                        }
+                        Err(e) => debug!("Failed to reuse {}: {e}", archived.display()),
+                    }
+                }
+
+                let _permit = downloads.acquire().await.unwrap();
+                if !space.wait(&shutdown).await {
+                    // left out like a failed download, retried on the next run
+                    pb.files.inc(1);
+                    return None;
+                }
+
                 let download_path = client.download(&url);
                 let result = download_path.await.map(|path| (url, path));
-                file_pb.inc(1);
-                result.inspect_err(|e| error!("Failed to download file: {e}"))
+                pb.files.inc(1);
+                if let Ok((url, path)) = &result {
+                    let bytes = path.metadata().map(|m| m.len()).unwrap_or_default();
+                    report.update(&campaign_id, |creator| {
+                        creator.files += 1;
+                        creator.bytes += bytes;
+                    });
+                    pb.emit(Event::FileDownloaded {
+                        creator: campaign_id.clone(),
+                        url: url.clone(),
+                        bytes,
+                    });
+                }
+                result
+                    .inspect_err(|e| error!("Failed to download file: {e}"))
+                    .ok()
             }))
-            .await
-            {
-                Ok(urls) => tx.send(urls.into_iter().collect()).unwrap(),
-                Err(e) => error!("Failed to receive file URLs: {e}"),
+            .await;
+
+            if tx.send(files.into_iter().flatten().collect()).is_err() {
+                error!("Failed to send downloaded files");
             }
         });
     }
@@ -54,6 +115,13 @@ pub async fn download_files(
     );
 }
 
+/// A staged copy of the archived file, hard linked when possible
+fn local_copy(archived: &Path, staging: &Path) -> io::Result<TempPath> {
+    let temp = staging_file(staging)?.into_temp_path();
+    link_or_copy(archived, &temp)?;
+    Ok(temp)
+}
+
 pub trait PatreonFileMeta
 where
     Self: Sized,
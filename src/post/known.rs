@@ -0,0 +1,41 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use post_archiver::manager::{PostArchiverConnection, PostArchiverManager};
+use post_archiver_utils::Result;
+
+/// Archived posts by source url, with the time they were updated
+///
+/// Read once per sync so that listing never waits on the imports holding the
+/// manager, and kept up to date by `sync_posts`.
+#[derive(Debug, Clone, Default)]
+pub struct KnownPosts(Arc<DashMap<String, DateTime<Utc>>>);
+
+impl KnownPosts {
+    pub fn load(manager: &PostArchiverManager<impl PostArchiverConnection>) -> Result<Self> {
+        let mut stmt = manager
+            .conn()
+            .prepare("SELECT source, updated FROM posts WHERE source IS NOT NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let known = Self::default();
+        for row in rows {
+            let (source, updated) = row?;
+            known.insert(source, updated);
+        }
+        Ok(known)
+    }
+
+    /// Whether the post is archived, at this update or a later one
+    pub fn contains(&self, source: &str, updated: &DateTime<Utc>) -> bool {
+        self.0.get(source).is_some_and(|known| *known >= *updated)
+    }
+
+    pub fn insert(&self, source: String, updated: DateTime<Utc>) {
+        let mut entry = self.0.entry(source).or_insert(updated);
+        if *entry < updated {
+            *entry = updated;
+        }
+    }
+}
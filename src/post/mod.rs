@@ -1,46 +1,68 @@
 mod body;
 pub mod file;
+pub mod known;
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
-    path::PathBuf,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    path::{Path, PathBuf},
 };
 
 use crate::{
-    api::PatreonClient,
+    api::{is_gone, PatreonClient},
     config::{ProgressSet, Strategy},
-    context::Context,
+    context::{CachedCampaign, Context, FailedPost},
     creator::sync_campaign,
+    dedupe::HashIndex,
+    event::Event,
+    hook::{Hook, HookPayload},
+    metrics::Pipeline,
     patreon::{comment::Comment, post::Post, Member},
+    report::Report,
+    shutdown::Shutdown,
+    webhook::{Notification, Webhook},
     Config, FilesEvent, Manager, PostsEvent, User,
 };
 use chrono::DateTime;
 use file::PatreonFileMeta;
-use futures::{future::join_all, try_join};
-use log::{debug, error, info, trace};
+use futures::{future::join_all, stream, try_join, StreamExt};
+use known::KnownPosts;
+use log::{debug, error, info, trace, warn};
 use plyne::{Input, Output};
 use post_archiver::{
     importer::{post::UnsyncPost, UnsyncCollection, UnsyncFileMeta, UnsyncTag},
-    manager::{PostArchiverConnection, PostArchiverManager},
-    AuthorId, PlatformId,
+    AuthorId, PlatformId, Post as ArchivedPost,
 };
 use post_archiver_utils::Result;
 use serde_json::json;
 use tempfile::TempPath;
 use tokio::{
-    fs::{create_dir_all, File, OpenOptions},
+    fs::{create_dir_all, remove_dir, remove_file, File, OpenOptions},
     io,
     sync::oneshot,
+    task::JoinSet,
 };
 
+/// Prefix of the tags recording the tiers of a post
+pub const TIER_TAG: &str = "tier:";
+/// Key of the file extra recording the position of a media in the gallery order
+pub const IMAGE_ORDER: &str = "image_order";
+
 pub fn filter_posts(
     config: &Config,
-    manager: &PostArchiverManager<impl PostArchiverConnection>,
+    known: &KnownPosts,
+    report: &Report,
+    campaign_id: &str,
     posts: Vec<Post>,
 ) -> Vec<Post> {
-    posts
+    let found = posts.len();
+    let posts = posts
         .into_iter()
         .filter(|post| config.filter_post(post))
+        .collect::<Vec<_>>();
+    let accepted = posts.len();
+
+    let posts = posts
+        .into_iter()
         .filter(|post| {
             if config.strategy() == Strategy::Force {
                 return true;
@@ -49,133 +71,319 @@ pub fn filter_posts(
             let updated = DateTime::parse_from_rfc3339(&post.published_at)
                 .unwrap()
                 .to_utc();
-            manager
-                .find_post_with_updated(&post.url, &updated)
-                .unwrap_or_else(|err| {
-                    error!("Failed to check post {}: {}", &post.url, err);
-                    None
-                })
-                .is_none()
+            !known.contains(&post.url, &updated)
         })
-        .collect()
+        .collect::<Vec<_>>();
+
+    report.update(campaign_id, |creator| {
+        creator.found += found;
+        creator.filtered += found - accepted;
+        creator.archived += accepted - posts.len();
+    });
+    posts
 }
 
+/// Campaigns listed at once, their requests still share the rate limit of the client
+const CONCURRENT_CAMPAIGNS: usize = 4;
+
 pub async fn list_posts(
-    mut campaign_pipeline: Output<Member>,
+    campaign_pipeline: Output<Member>,
     posts_pipeline: Input<PostsEvent>,
     files_pipeline: Input<FilesEvent>,
     user: &User,
     config: &Config,
     client: &PatreonClient,
-    manager: &Manager,
+    known: &KnownPosts,
     context: &Context,
     pb: &ProgressSet,
+    report: &Report,
+    shutdown: &Shutdown,
 ) {
-    while let Some(member) = campaign_pipeline.recv().await {
-        let campaign_id = member.campaign.id.clone();
-        let cents = member.cents();
-
-        info!("Loading posts of campaign {campaign_id}");
+    let members = stream::unfold(campaign_pipeline, |mut pipeline| async move {
+        pipeline.recv().await.map(|member| (member, pipeline))
+    });
+    members
+        .for_each_concurrent(CONCURRENT_CAMPAIGNS, |member| async {
+            pb.metrics().queued(Pipeline::Campaign, -1);
+            if shutdown.requested() {
+                return;
+            }
+            list_campaign(
+                member,
+                &posts_pipeline,
+                &files_pipeline,
+                user,
+                config,
+                client,
+                known,
+                context,
+                pb,
+                report,
+                shutdown,
+            )
+            .await;
+            pb.creators.inc(1);
+        })
+        .await;
 
-        let mut campaign_record = context.campaigns.entry(campaign_id.clone()).or_default();
+    info!(
+        "Creators processed: {}/{} creators",
+        pb.creators.position(),
+        pb.creators.length().unwrap_or_default()
+    );
+}
 
-        let last_published = campaign_record
-            .last_published(cents)
-            .filter(|_| config.strategy() == Strategy::Increment);
+async fn list_campaign(
+    member: Member,
+    posts_pipeline: &Input<PostsEvent>,
+    files_pipeline: &Input<FilesEvent>,
+    user: &User,
+    config: &Config,
+    client: &PatreonClient,
+    known: &KnownPosts,
+    context: &Context,
+    pb: &ProgressSet,
+    report: &Report,
+    shutdown: &Shutdown,
+) {
+    let campaign_id = member.campaign.id.clone();
+    let cents = member.cents();
+
+    info!("Loading posts of campaign {campaign_id}");
+    report.start(&campaign_id, &member.campaign.name);
+    pb.emit(Event::CreatorStarted {
+        id: campaign_id.clone(),
+        name: member.campaign.name.clone(),
+    });
+
+    let last_published = context
+        .campaigns
+        .get(&campaign_id)
+        .and_then(|record| record.last_published(cents))
+        .filter(|_| config.strategy() == Strategy::Increment);
+
+    let mut next_url = Some(client.get_posts_url(user, &campaign_id));
+    let mut max_timestamp = 0i64;
+    let mut stop = false;
+    let mut completed = true;
+    let mut total = 0usize;
+
+    // posts failed on the previous runs are retried first, whatever the strategy
+    let failed = context.failed_posts(&campaign_id);
+    if !failed.is_empty() {
+        info!("Retrying {} failed posts ({campaign_id})", failed.len());
+    }
 
-        let mut next_url = Some(client.get_posts_url(user, &campaign_id));
-        let mut max_timestamp = 0i64;
-        let mut stop = false;
-        let mut total = 0usize;
+    let mut retries = vec![];
+    for post_id in failed.iter() {
+        match client.get_post(post_id).await {
+            Ok(post) => retries.push(post),
+            Err(e) if is_gone(&e) => {
+                warn!("Giving up failed post {post_id}, it is not accessible anymore: {e}");
+                context.resolve_failure(post_id);
+            }
+            Err(e) => {
+                error!("Failed to load failed post {post_id}: {e}");
+                let attempts = context.record_attempt(post_id, e.to_string());
+                if attempts >= FailedPost::MAX_ATTEMPTS {
+                    warn!("Giving up failed post {post_id} after {attempts} attempts");
+                    context.resolve_failure(post_id);
+                }
+            }
+        }
+    }
+    let found = retries.len();
+    retries.retain(|post| {
+        let accept = config.filter_post(post);
+        if !accept {
+            context.resolve_failure(&post.id);
+        }
+        accept
+    });
+    report.update(&campaign_id, |creator| {
+        creator.found += found;
+        creator.filtered += found - retries.len();
+    });
+    total += retries.len();
+    pb.posts.inc_length(retries.len() as u64);
+    join_all(
+        retries
+            .into_iter()
+            .map(|post| queue_post(post, client, files_pipeline, posts_pipeline, pb)),
+    )
+    .await;
+
+    while let Some(url) = next_url.take() {
+        if shutdown.requested() {
+            completed = false;
+            break;
+        }
 
-        let manager_guard = manager.lock().await;
-        while let Some(url) = next_url.take() {
-            let Ok((posts, next)) = client.get_posts(&url).await else {
-                error!("Failed to load posts of campaign {campaign_id}");
-                break;
-            };
+        let Ok((posts, next)) = client.get_posts(&url).await else {
+            error!("Failed to load posts of campaign {campaign_id}");
+            completed = false;
+            break;
+        };
 
-            let posts: Vec<_> = posts
-                .into_iter()
-                .filter_map(|post| {
-                    let published_timestamp = DateTime::parse_from_rfc3339(&post.published_at)
-                        .unwrap()
-                        .timestamp();
-                    max_timestamp = max_timestamp.max(published_timestamp);
-
-                    match last_published {
-                        Some(t) if published_timestamp <= t => {
-                            stop = true;
-                            None
-                        }
-                        _ => Some(post),
+        let posts: Vec<_> = posts
+            .into_iter()
+            .filter_map(|post| {
+                let published_timestamp = DateTime::parse_from_rfc3339(&post.published_at)
+                    .unwrap()
+                    .timestamp();
+                max_timestamp = max_timestamp.max(published_timestamp);
+
+                match last_published {
+                    Some(t) if published_timestamp <= t => {
+                        stop = true;
+                        None
                     }
-                })
-                .collect();
-
-            if stop {
-                debug!("Skipping remaining posts for campaign {campaign_id}");
-            }
-
-            next_url = if stop { None } else { next };
+                    _ => Some(post),
+                }
+            })
+            .collect();
 
-            let posts = filter_posts(config, &*manager_guard, posts);
-            total += posts.len();
-            pb.posts.inc_length(posts.len() as u64);
+        if stop {
+            debug!("Skipping remaining posts for campaign {campaign_id}");
+        }
 
-            let posts = posts
-                .into_iter()
-                .map(async |post| {
-                    let comments = match post.comment_count {
-                        0 => vec![],
-                        _ => client.get_comments(&post.id).await.unwrap_or_else(|err| {
-                            error!("Failed to get comments of post {}: {}", &post.id, err);
-                            vec![]
-                        }),
-                    };
+        next_url = if stop { None } else { next };
 
-                    let (tx, rx) = oneshot::channel();
+        let mut posts = posts;
+        posts.retain(|post| !failed.contains(&post.id));
+        let posts = filter_posts(config, known, report, &campaign_id, posts);
+        total += posts.len();
+        pb.posts.inc_length(posts.len() as u64);
 
-                    let contents = post.files();
-                    files_pipeline.send((contents, tx)).unwrap();
-                    posts_pipeline.send((post, comments, rx)).unwrap();
-                })
-                .collect::<Vec<_>>();
+        let posts = posts
+            .into_iter()
+            .map(|post| queue_post(post, client, files_pipeline, posts_pipeline, pb))
+            .collect::<Vec<_>>();
 
-            join_all(posts).await;
-        }
-        drop(manager_guard);
+        join_all(posts).await;
+    }
 
-        info!("Found {} posts ({campaign_id})", total);
-        campaign_record.update(max_timestamp, cents);
-        pb.creators.inc(1);
+    info!("Found {} posts ({campaign_id})", total);
+    if completed {
+        // committed by `sync_posts` once every post queued above is imported
+        let checkpoint = CachedCampaign::new(max_timestamp, cents);
+        posts_pipeline
+            .send(PostsEvent::Checkpoint(campaign_id, checkpoint))
+            .unwrap();
+        pb.metrics().queued(Pipeline::Posts, 1);
     }
-    info!(
-        "Creators processed: {}/{} creators",
-        pb.creators.position(),
-        pb.creators.length().unwrap_or_default()
-    );
+}
+
+async fn queue_post(
+    post: Post,
+    client: &PatreonClient,
+    files_pipeline: &Input<FilesEvent>,
+    posts_pipeline: &Input<PostsEvent>,
+    pb: &ProgressSet,
+) {
+    let comments = match post.comment_count {
+        0 => Some(vec![]),
+        _ => client
+            .get_comments(&post.id)
+            .await
+            .inspect_err(|err| error!("Failed to get comments of post {}: {}", &post.id, err))
+            .ok(),
+    };
+
+    let (tx, rx) = oneshot::channel();
+
+    let media_ids = post.media_ids();
+    // the cover is often a media of the post too, downloaded once for both
+    let mut urls = HashSet::new();
+    let contents = post
+        .files()
+        .into_iter()
+        .filter(|url| urls.insert(url.clone()))
+        .map(|url| {
+            let id = media_ids.get(&url).cloned();
+            (url, id)
+        })
+        .collect();
+    let campaign_id = post.campaign.id.clone();
+    pb.emit(Event::PostQueued {
+        id: post.id.clone(),
+        creator: campaign_id.clone(),
+        title: post.title.clone(),
+    });
+    files_pipeline.send((campaign_id, contents, tx)).unwrap();
+    posts_pipeline
+        .send(PostsEvent::Post(Box::new(post), comments, rx))
+        .unwrap();
+    pb.metrics().queued(Pipeline::Files, 1);
+    pb.metrics().queued(Pipeline::Posts, 1);
 }
 
 pub async fn sync_posts(
     mut posts_pipeline: Output<PostsEvent>,
+    config: &Config,
     manager: &Manager,
+    known: &KnownPosts,
+    context: &Context,
     pb: &ProgressSet,
+    report: &Report,
+    webhook: &Webhook,
 ) {
+    let post_hook = Hook::new(config.post_hook());
+    let file_hook = Hook::new(config.file_hook());
+    let hashes = config.dedupe().then(|| HashIndex::load(config.output()));
+    // webhooks and hooks, awaited before returning
+    let mut background = JoinSet::new();
+    let record_failure = |post: &str, campaign: &str, reason: String, files: Vec<String>| {
+        pb.emit(Event::PostFailed {
+            id: post.to_string(),
+            creator: campaign.to_string(),
+            reason: reason.clone(),
+        });
+        let attempts = context.record_failure(post, campaign, reason, files);
+        if attempts >= FailedPost::MAX_ATTEMPTS {
+            warn!("Giving up failed post {post} after {attempts} attempts");
+            context.resolve_failure(post);
+        }
+        report.update(campaign, |creator| creator.failed += 1);
+    };
+
     let mut authors = HashMap::new();
-    'post: while let Some((post, comments, rx)) = posts_pipeline.recv().await {
+    'post: while let Some(event) = posts_pipeline.recv().await {
+        pb.metrics().queued(Pipeline::Posts, -1);
+        let (post, comments, rx) = match event {
+            PostsEvent::Post(post, comments, rx) => (*post, comments, rx),
+            PostsEvent::Checkpoint(campaign_id, checkpoint) => {
+                context
+                    .campaigns
+                    .entry(campaign_id.clone())
+                    .or_default()
+                    .update(checkpoint.published, checkpoint.cents);
+                if let Err(e) = context.save(config.output()) {
+                    error!("Failed to save context: {e}");
+                }
+                if let Some(Err(e)) = hashes.as_ref().map(|h| h.save(config.output())) {
+                    error!("Failed to save hash index: {e}");
+                }
+                report.finish(&campaign_id);
+                pb.emit(Event::CreatorFinished { id: campaign_id });
+                continue;
+            }
+        };
+
         let mut manager = manager.lock().await;
 
         let platform = manager.import_platform("patreon".to_string()).unwrap();
 
+        let post_id = post.id.clone();
         let campaign = post.campaign.clone();
         let campaign_id = campaign.id.clone();
-        let author = match authors.entry(campaign_id) {
+        let author = match authors.entry(campaign_id.clone()) {
             Entry::Occupied(occupied_entry) => *occupied_entry.get(),
             Entry::Vacant(vacant_entry) => match sync_campaign(&manager, platform, &campaign) {
                 Ok(author) => *vacant_entry.insert(author),
                 Err(e) => {
                     error!("Failed to sync creator for post: {} {:?}", post.id, e);
+                    record_failure(&post_id, &campaign_id, e.to_string(), vec![]);
                     continue;
                 }
             },
@@ -184,33 +392,141 @@ pub async fn sync_posts(
         let tx = manager.transaction().unwrap();
 
         let title = post.title.clone();
-        let post = conversion_post(platform, author, post, comments);
+        let mut notification = Notification::new(&post);
+        let mut payload = HookPayload::new(&post);
+        let comments_loaded = comments.is_some();
+        let media_ids = post.media_ids();
+        let published = DateTime::parse_from_rfc3339(&post.published_at)
+            .unwrap()
+            .to_utc();
+        let post = conversion_post(platform, author, post, comments.unwrap_or_default());
         let source = post.source.clone();
-
-        let Ok((_, _, _, files)) = tx.import_post(post, true) else {
-            error!("Failed to import post: {source}");
-            continue;
+        // re-imports and retries of archived posts are not announced again
+        let is_new = tx.find_post(&source).is_ok_and(|id| id.is_none());
+
+        let (archive_id, _, _, files) = match tx.import_post(post, true) {
+            Ok(imported) => imported,
+            Err(e) => {
+                error!("Failed to import post: {source}");
+                record_failure(&post_id, &campaign_id, e.to_string(), vec![]);
+                continue;
+            }
         };
 
         let Ok(mut file_map) = rx.await else {
             error!("Failed to receive file map for post: {source}");
+            let reason = "Failed to receive file map".to_string();
+            record_failure(&post_id, &campaign_id, reason, vec![]);
             continue;
         };
 
+        let missing = files
+            .iter()
+            .filter(|(_, url)| !file_map.contains_key(url))
+            .map(|(_, url)| url.clone())
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            error!("Aborting post import due to file errors: {source}");
+            let reason = "Failed to download files".to_string();
+            record_failure(&post_id, &campaign_id, reason, missing);
+            continue;
+        }
+
+        payload.archive_id = archive_id.raw();
+        payload.directory = config.output().join(ArchivedPost::directory(archive_id));
+
+        let file_count = files.len();
         let mut create_dir = true;
+        let mut saved = Vec::with_capacity(file_count);
+        // the files which did not exist before, removed when the post is rolled back
+        let mut created = vec![];
+        let mut media = vec![];
+        // run once the post is committed, the paths are not archived before
+        let mut file_payloads = vec![];
+        let mut uses = HashMap::<String, usize>::new();
+        for (_, url) in &files {
+            *uses.entry(url.clone()).or_default() += 1;
+        }
         for (path, url) in files {
-            if let Err(e) = save_file(&mut file_map, &path, &url, create_dir).await {
+            let remaining = uses.get_mut(&url).unwrap();
+            *remaining -= 1;
+            let last = *remaining == 0;
+            let exists = path.exists();
+            if let Err(e) = save_file(&mut file_map, &path, &url, create_dir, last).await {
                 error!("Failed to save file {}: {}", path.display(), e);
                 error!("Aborting post import due to file errors: {source}");
+                for path in created {
+                    remove_file(path).await.ok();
+                }
+                if let Some(folder) = path.parent() {
+                    remove_dir(folder).await.ok();
+                }
+                record_failure(&post_id, &campaign_id, e.to_string(), vec![url]);
                 continue 'post;
             };
             create_dir = false;
+            if !exists {
+                created.push(path.clone());
+            }
+            if let Some(id) = media_ids.get(&url) {
+                media.push((id, path.clone()));
+            }
+
+            if file_hook.is_enabled() {
+                file_payloads.push(payload.file(&path, &url));
+            }
+            saved.push(path);
         }
 
         tx.commit().unwrap();
         info!("Post imported: {title}");
+        report.update(&campaign_id, |creator| creator.imported += 1);
+        known.insert(source.clone(), published);
+        for (id, path) in media {
+            context.record_media(id, &path, config.output());
+        }
+
+        if let Some(hashes) = &hashes {
+            let deduplicated = deduplicate(hashes, config.output(), &saved).await;
+            report.update(&campaign_id, |creator| creator.deduplicated += deduplicated);
+        }
+
+        if comments_loaded {
+            context.resolve_failure(&post_id);
+        } else {
+            // imported without comments, the post is fetched again on the next run
+            let reason = "Failed to load comments".to_string();
+            let attempts = context.record_failure(&post_id, &campaign_id, reason, vec![]);
+            if attempts >= FailedPost::MAX_ATTEMPTS {
+                warn!("Giving up the comments of post {post_id} after {attempts} attempts");
+                context.resolve_failure(&post_id);
+            }
+        }
 
         pb.posts.inc(1);
+        pb.emit(Event::PostImported {
+            id: post_id,
+            creator: campaign_id,
+            title,
+            files: file_count,
+        });
+
+        if webhook.is_enabled() && is_new {
+            notification.files = file_count;
+            background.spawn(webhook.send(notification));
+        }
+
+        for file_payload in file_payloads {
+            background.spawn(file_hook.run(file_payload));
+        }
+        if post_hook.is_enabled() {
+            payload.files = saved;
+            background.spawn(post_hook.run(payload));
+        }
+    }
+    background.join_all().await;
+    if let Some(Err(e)) = hashes.as_ref().map(|h| h.save(config.output())) {
+        error!("Failed to save hash index: {e}");
     }
 
     info!(
@@ -219,96 +535,147 @@ pub async fn sync_posts(
         pb.posts.length().unwrap_or_default()
     );
 
-    fn conversion_post(
-        platform: PlatformId,
-        author: AuthorId,
-        post: Post,
-        comments: Vec<Comment>,
-    ) -> UnsyncPost<String> {
-        let mut tags = vec![];
-        if post.is_free() {
-            tags.push(UnsyncTag {
-                name: "free".to_string(),
-                platform: None,
-            });
-        }
-
-        let collections = post
-            .user_defined_tags
-            .iter()
-            .map(|tag| {
-                UnsyncCollection::new(
-                    tag.value.clone(),
-                    format!(
-                        "{}/posts?filters[tag]={}",
-                        post.campaign.url,
-                        urlencoding::encode(&tag.value)
-                    ),
-                )
-            })
-            .collect();
-
-        let thumb = post.image.clone().map(|image| {
-            let mut meta = if image.url.starts_with("https://www.patreon.com/media-u/v3/") {
-                // default thumb url
-                UnsyncFileMeta::new("thumb.jpg".to_string(), "image/jpeg".to_string(), image.url)
-            } else {
-                UnsyncFileMeta::from_url(image.url)
-            };
-            meta.extra = HashMap::from([
-                ("width".to_string(), json!(image.width)),
-                ("height".to_string(), json!(image.height)),
-            ]);
-            meta
-        });
-
-        let content = post.contents();
-
-        let comments = comments.into_iter().map(|c| c.into()).collect();
-
-        let published = DateTime::parse_from_rfc3339(&post.published_at)
-            .unwrap()
-            .to_utc();
-
-        UnsyncPost::new(platform, post.url, post.title, content)
-            .published(published)
-            .updated(published)
-            .authors(vec![author])
-            .tags(tags)
-            .thumb(thumb)
-            .comments(comments)
-            .collections(collections)
-    }
-
+    /// The temp file is renamed into place on its `last` path, and copied to the
+    /// others, as a url can be saved to several paths of the post
     async fn save_file(
         file_map: &mut HashMap<String, TempPath>,
         path: &PathBuf,
         url: &str,
         create_dir: bool,
+        last: bool,
     ) -> Result<()> {
         if create_dir {
             let path = path.parent().unwrap();
             create_dir_all(path).await?;
         }
 
-        let temp = file_map.remove(url).ok_or(io::Error::new(
-            io::ErrorKind::NotFound,
-            format!("File not found in map: {url}"),
-        ))?;
+        let not_found = || {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("File not found in map: {url}"),
+            )
+        };
+
+        if !last {
+            let temp = file_map.get(url).ok_or_else(not_found)?;
+            copy_file(temp, path).await?;
+            trace!("File saved: {url} -> {}", path.display());
+            return Ok(());
+        }
+
+        // the staging folder is on the output filesystem, unless configured otherwise
+        let temp = file_map.remove(url).ok_or_else(not_found)?;
+        match temp.persist(path) {
+            Ok(()) => trace!("File moved: {url} -> {}", path.display()),
+            Err(e) => {
+                debug!("Failed to move {url}, copying: {}", e.error);
+                copy_file(&e.path, path).await?;
+                trace!("File saved: {url} -> {}", path.display());
+            }
+        }
+        Ok(())
+    }
+
+    async fn copy_file(src: &Path, dst: &Path) -> Result<()> {
+        // never write through a hard link shared with another post
+        remove_file(dst).await.ok();
 
         let mut open_options = OpenOptions::new();
         let (mut src, mut dst) = try_join!(
-            File::open(&temp),
+            File::open(src),
             open_options
                 .create(true)
                 .write(true)
                 .truncate(true)
-                .open(&path)
+                .open(dst)
         )?;
 
         io::copy(&mut src, &mut dst).await?;
-        trace!("File saved: {url} -> {}", path.display());
-
         Ok(())
     }
 }
+
+/// Hard link the saved files to the archived ones with the same content, off the
+/// runtime as the files are read whole. Returns the bytes saved.
+async fn deduplicate(hashes: &HashIndex, output: &Path, paths: &[PathBuf]) -> u64 {
+    let (hashes, output, paths) = (hashes.clone(), output.to_path_buf(), paths.to_vec());
+    tokio::task::spawn_blocking(move || {
+        paths
+            .iter()
+            .map(|path| {
+                hashes.store(&output, path).unwrap_or_else(|e| {
+                    warn!("Failed to deduplicate {}: {e}", path.display());
+                    0
+                })
+            })
+            .sum()
+    })
+    .await
+    .unwrap_or_default()
+}
+
+/// Map a Patreon post to the importer, shared by the sync and `import-dir`
+pub fn conversion_post(
+    platform: PlatformId,
+    author: AuthorId,
+    post: Post,
+    comments: Vec<Comment>,
+) -> UnsyncPost<String> {
+    let mut tags = vec![];
+    if post.is_free() {
+        tags.push(UnsyncTag {
+            name: "free".to_string(),
+            platform: None,
+        });
+    }
+    tags.extend(post.tiers().into_iter().map(|tier| UnsyncTag {
+        name: format!("{TIER_TAG}{tier}"),
+        platform: Some(platform),
+    }));
+
+    let collections = post
+        .user_defined_tags
+        .iter()
+        .map(|tag| {
+            UnsyncCollection::new(
+                tag.value.clone(),
+                format!(
+                    "{}/posts?filters[tag]={}",
+                    post.campaign.url,
+                    urlencoding::encode(&tag.value)
+                ),
+            )
+        })
+        .collect();
+
+    let thumb = post.image.clone().map(|image| {
+        let mut meta = if image.url.starts_with("https://www.patreon.com/media-u/v3/") {
+            // default thumb url
+            UnsyncFileMeta::new("thumb.jpg".to_string(), "image/jpeg".to_string(), image.url)
+        } else {
+            UnsyncFileMeta::from_url(image.url)
+        };
+        meta.extra = HashMap::from([
+            ("width".to_string(), json!(image.width)),
+            ("height".to_string(), json!(image.height)),
+        ]);
+        meta
+    });
+
+    let content = post.contents();
+
+    let comments = comments.into_iter().map(|c| c.into()).collect();
+
+    let published = DateTime::parse_from_rfc3339(&post.published_at)
+        .unwrap()
+        .to_utc();
+
+    UnsyncPost::new(platform, post.url, post.title, content)
+        .published(published)
+        .updated(published)
+        .authors(vec![author])
+        .tags(tags)
+        .thumb(thumb)
+        .comments(comments)
+        .collections(collections)
+}
@@ -0,0 +1,186 @@
+use std::{
+    fs, io,
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use log::info;
+use serde::Serialize;
+
+/// Per-creator statistics collected during a run
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    creators: Arc<DashMap<String, CreatorReport>>,
+    started: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CreatorReport {
+    pub id: String,
+    pub name: String,
+    /// Posts listed from Patreon
+    pub found: usize,
+    pub imported: usize,
+    /// Posts rejected by the configured filters
+    pub filtered: usize,
+    /// Posts which are already in the archive
+    pub archived: usize,
+    pub failed: usize,
+    pub files: usize,
+    pub bytes: u64,
+    /// Bytes of the files hard linked to an identical archived file
+    pub deduplicated: u64,
+    #[serde(serialize_with = "serialize_secs")]
+    pub elapsed: Duration,
+    #[serde(skip)]
+    started: Option<Instant>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self {
+            started: Some(Instant::now()),
+            ..Default::default()
+        }
+    }
+
+    pub fn start(&self, id: &str, name: &str) {
+        let mut creator = self.creators.entry(id.to_string()).or_default();
+        creator.id = id.to_string();
+        creator.name = name.to_string();
+        creator.started.get_or_insert_with(Instant::now);
+    }
+
+    pub fn finish(&self, id: &str) {
+        self.update(id, |creator| {
+            if let Some(started) = creator.started {
+                creator.elapsed = started.elapsed();
+            }
+        });
+    }
+
+    /// The entry is only borrowed for the duration of `f`, never across an await point
+    pub fn update(&self, id: &str, f: impl FnOnce(&mut CreatorReport)) {
+        f(&mut self.creators.entry(id.to_string()).or_default())
+    }
+
+    /// Creators sorted by name, with the elapsed time of unfinished ones filled in
+    pub fn creators(&self) -> Vec<CreatorReport> {
+        let mut creators = self
+            .creators
+            .iter()
+            .map(|creator| {
+                let mut creator = creator.clone();
+                if creator.elapsed.is_zero() {
+                    creator.elapsed = creator.started.map(|s| s.elapsed()).unwrap_or_default();
+                }
+                creator
+            })
+            .collect::<Vec<_>>();
+        creators.sort_by(|a, b| a.name.cmp(&b.name));
+        creators
+    }
+
+    pub fn total(&self) -> CreatorReport {
+        let mut total = CreatorReport {
+            name: "Total".to_string(),
+            elapsed: self.started.map(|s| s.elapsed()).unwrap_or_default(),
+            ..Default::default()
+        };
+        for creator in self.creators.iter() {
+            total.found += creator.found;
+            total.imported += creator.imported;
+            total.filtered += creator.filtered;
+            total.archived += creator.archived;
+            total.failed += creator.failed;
+            total.files += creator.files;
+            total.bytes += creator.bytes;
+            total.deduplicated += creator.deduplicated;
+        }
+        total
+    }
+
+    pub fn display(&self) {
+        let creators = self.creators();
+        let total = self.total();
+
+        let name_width = creators
+            .iter()
+            .map(|creator| creator.name.chars().count())
+            .chain([6])
+            .max()
+            .unwrap();
+
+        let separator = format!(
+            "+-{}-+--------+----------+----------+----------+--------+--------+------------+----------+",
+            "-".repeat(name_width)
+        );
+        info!("{separator}");
+        info!(
+            "| {:name_width$} |  Found | Imported | Filtered | Archived | Failed |  Files |       Size |     Time |",
+            "Name"
+        );
+        info!("{separator}");
+        let row = |creator: &CreatorReport| {
+            info!(
+                "| {:name_width$} | {:>6} | {:>8} | {:>8} | {:>8} | {:>6} | {:>6} | {:>10} | {:>8} |",
+                creator.name,
+                creator.found,
+                creator.imported,
+                creator.filtered,
+                creator.archived,
+                creator.failed,
+                creator.files,
+                format_bytes(creator.bytes),
+                format_duration(creator.elapsed),
+            )
+        };
+        creators.iter().for_each(row);
+        info!("{separator}");
+        row(&total);
+        info!("{separator}");
+        if total.deduplicated > 0 {
+            info!("Deduplicated: {}", format_bytes(total.deduplicated));
+        }
+        info!("");
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        #[derive(Serialize)]
+        struct ReportJson {
+            creators: Vec<CreatorReport>,
+            total: CreatorReport,
+        }
+
+        let json = serde_json::to_string_pretty(&ReportJson {
+            creators: self.creators(),
+            total: self.total(),
+        })?;
+        fs::write(path, json)
+    }
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_f64(duration.as_secs_f64())
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.2} {}", UNITS[unit]),
+    }
+}
+
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
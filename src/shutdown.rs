@@ -0,0 +1,73 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use log::warn;
+use tokio::sync::Notify;
+
+/// Graceful shutdown flag shared by every task.
+///
+/// The first SIGINT/SIGTERM stops queuing new work, the second one aborts immediately.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown {
+    requested: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl Shutdown {
+    pub fn listen() -> Self {
+        let shutdown = Self::default();
+
+        let handle = shutdown.clone();
+        tokio::spawn(async move {
+            signal().await;
+            warn!("Interrupted, finishing in-flight posts (interrupt again to abort)");
+            handle.request();
+
+            signal().await;
+            warn!("Aborted");
+            std::process::exit(130);
+        });
+
+        shutdown
+    }
+
+    pub fn request(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn requested(&self) -> bool {
+        self.requested.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once a shutdown has been requested
+    pub async fn wait(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.requested() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {},
+        _ = terminate.recv() => {},
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Failed to listen for Ctrl-C");
+}
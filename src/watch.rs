@@ -0,0 +1,174 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::{DashMap, DashSet};
+use tokio::sync::Notify;
+
+use crate::{config::Config, patreon::Member};
+
+/// Decides which creators are due in watch mode.
+///
+/// Outside of watch mode every creator is always due.
+#[derive(Debug, Default)]
+pub struct Schedule {
+    interval: Option<Duration>,
+    jitter: Duration,
+    creators: HashMap<String, Duration>,
+    next: DashMap<String, Instant>,
+    /// Campaigns forced since the last round, until a member consumes them
+    forced: DashSet<String>,
+    /// The round failed before the due creators were listed
+    failed: AtomicBool,
+}
+
+impl Schedule {
+    /// Delay before a failed round is tried again
+    const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+    pub fn new(config: &Config) -> Self {
+        Self {
+            interval: config.watch(),
+            jitter: config.watch_jitter(),
+            creators: config.watch_creators().iter().cloned().collect(),
+            next: DashMap::new(),
+            forced: DashSet::new(),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_due(&self, member: &Member) -> bool {
+        self.next
+            .get(&member.campaign.id)
+            .is_none_or(|next| *next <= Instant::now())
+    }
+
+    /// Schedule the next sync of the creator, called when its posts are queued
+    pub fn synced(&self, member: &Member) {
+        self.forced.remove(&member.campaign.id);
+        let Some(interval) = self.interval(member) else {
+            return;
+        };
+
+        let jitter = Duration::from_millis(fastrand::u64(..=self.jitter.as_millis() as u64));
+        let next = Instant::now() + interval + jitter;
+        self.next.insert(member.campaign.id.clone(), next);
+    }
+
+    /// Make the campaigns due now, whatever their interval
+    pub fn force(&self, campaigns: HashSet<String>) {
+        let now = Instant::now();
+        for campaign in campaigns {
+            self.next.insert(campaign.clone(), now);
+            self.forced.insert(campaign);
+        }
+    }
+
+    /// The creators due were not listed, e.g. the members failed to load
+    pub fn fail(&self) {
+        self.failed.store(true, Ordering::SeqCst);
+    }
+
+    /// How long to sleep before the next creator is due
+    pub fn next_delay(&self) -> Duration {
+        // the creators due are still due, retried without spinning on the error
+        if self.failed.swap(false, Ordering::SeqCst) {
+            return self.earliest().max(Self::RETRY_DELAY);
+        }
+
+        // left by a member filtered out or gone, they would be due again right away
+        for campaign in self.forced.iter() {
+            self.next.remove(campaign.key());
+        }
+        self.forced.clear();
+        self.earliest()
+    }
+
+    fn earliest(&self) -> Duration {
+        let now = Instant::now();
+        self.next
+            .iter()
+            .map(|next| next.saturating_duration_since(now))
+            .min()
+            .or(self.interval)
+            .unwrap_or_default()
+    }
+
+    fn interval(&self, member: &Member) -> Option<Duration> {
+        let interval = self.interval?;
+        let creator = self.creators.get(&member.campaign.id).or_else(|| {
+            self.creators
+                .get(member.campaign.url.split('/').next_back()?)
+        });
+        Some(creator.copied().unwrap_or(interval))
+    }
+}
+
+/// Campaigns requested to sync as soon as possible, e.g. from the control server
+#[derive(Debug, Clone, Default)]
+pub struct SyncQueue {
+    campaigns: Arc<Mutex<HashSet<String>>>,
+    notify: Arc<Notify>,
+}
+
+impl SyncQueue {
+    pub fn push(&self, campaigns: impl IntoIterator<Item = String>) {
+        self.campaigns.lock().unwrap().extend(campaigns);
+        self.notify.notify_one();
+    }
+
+    pub fn take(&self) -> HashSet<String> {
+        std::mem::take(&mut *self.campaigns.lock().unwrap())
+    }
+
+    /// Resolves once campaigns have been pushed, even if it happened before waiting
+    pub async fn wait(&self) {
+        // the permit of a push already taken by the last round wakes up once for nothing
+        loop {
+            let notified = self.notify.notified();
+            if !self.campaigns.lock().unwrap().is_empty() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Parse `<CREATOR>=<INTERVAL>`, e.g. `somecreator=30m`
+pub fn parse_creator_interval(value: &str) -> Result<(String, Duration), String> {
+    let (creator, interval) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <CREATOR>=<INTERVAL>, got `{value}`"))?;
+    let interval = humantime::parse_duration(interval).map_err(|e| e.to_string())?;
+    Ok((creator.to_string(), interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creator_intervals() {
+        assert_eq!(
+            parse_creator_interval("somecreator=30m"),
+            Ok(("somecreator".to_string(), Duration::from_secs(30 * 60)))
+        );
+        assert_eq!(
+            parse_creator_interval("somecreator=1h 30m"),
+            Ok(("somecreator".to_string(), Duration::from_secs(90 * 60)))
+        );
+    }
+
+    #[test]
+    fn invalid_creator_intervals() {
+        assert!(parse_creator_interval("somecreator").is_err());
+        assert!(parse_creator_interval("somecreator=").is_err());
+        assert!(parse_creator_interval("somecreator=soon").is_err());
+        assert!(parse_creator_interval("30m").is_err());
+    }
+}
@@ -0,0 +1,166 @@
+use std::{fs, future::Future, io, path::Path, sync::Arc, time::Duration};
+
+use log::{error, trace, warn};
+use reqwest::{header, Client};
+use serde::Serialize;
+
+use crate::patreon::post::Post;
+
+/// Outgoing notification for every newly archived post
+#[derive(Debug, Clone, Default)]
+pub struct Webhook(Option<Arc<WebhookInner>>);
+
+#[derive(Debug)]
+struct WebhookInner {
+    url: String,
+    /// Body with `{{creator}}`, `{{creator_id}}`, `{{title}}`, `{{url}}`, `{{published}}`,
+    /// `{{tags}}` and `{{files}}` placeholders
+    template: Option<String>,
+    client: Client,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub creator: String,
+    pub creator_id: String,
+    pub title: String,
+    pub url: String,
+    pub published: String,
+    pub tags: Vec<String>,
+    pub files: usize,
+}
+
+impl Notification {
+    pub fn new(post: &Post) -> Self {
+        Self {
+            creator: post.campaign.name.clone(),
+            creator_id: post.campaign.id.clone(),
+            title: post.title.clone(),
+            url: post.url.clone(),
+            published: post.published_at.clone(),
+            tags: post
+                .user_defined_tags
+                .iter()
+                .map(|tag| tag.value.clone())
+                .collect(),
+            files: 0,
+        }
+    }
+}
+
+impl Webhook {
+    const RETRY_LIMIT: u32 = 3;
+    /// A hanging endpoint would otherwise hold the end of every sync
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    pub fn new(url: Option<&str>, template: Option<&Path>) -> io::Result<Self> {
+        let Some(url) = url else {
+            return Ok(Self::default());
+        };
+
+        let template = template.map(fs::read_to_string).transpose()?;
+        Ok(Self(Some(Arc::new(WebhookInner {
+            url: url.to_string(),
+            template,
+            client: Client::builder()
+                .timeout(Self::TIMEOUT)
+                .build()
+                .map_err(io::Error::other)?,
+        }))))
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// The returned future owns everything it needs, so it can be spawned
+    pub fn send(&self, notification: Notification) -> impl Future<Output = ()> + 'static {
+        let inner = self.0.clone();
+        async move {
+            let Some(inner) = inner else {
+                return;
+            };
+
+            let body = match &inner.template {
+                Some(template) => render(template, &notification),
+                None => serde_json::to_string(&notification).unwrap(),
+            };
+
+            for attempt in 0..=Self::RETRY_LIMIT {
+                if attempt > 0 {
+                    tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+                }
+
+                let result = inner
+                    .client
+                    .post(&inner.url)
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(body.clone())
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+
+                // the timeout is not named by the error of reqwest
+                let result = result.map_err(|e| {
+                    if e.is_timeout() {
+                        format!("timed out after {}s", Self::TIMEOUT.as_secs())
+                    } else {
+                        e.to_string()
+                    }
+                });
+                match result {
+                    Ok(_) => {
+                        trace!("Webhook sent: {}", notification.url);
+                        return;
+                    }
+                    Err(e) if attempt < Self::RETRY_LIMIT => {
+                        warn!(
+                            "Failed to send webhook for {}: {e}. Retrying...",
+                            notification.url
+                        )
+                    }
+                    Err(e) => error!("Failed to send webhook for {}: {e}", notification.url),
+                }
+            }
+        }
+    }
+}
+
+/// Fill the placeholders, escaped to be embedded in a JSON string. The template is
+/// scanned once, so a value containing a placeholder is never filled in turn, and
+/// unknown placeholders are kept as they are.
+fn render(template: &str, notification: &Notification) -> String {
+    fn escape(value: &str) -> String {
+        let json = serde_json::to_string(value).unwrap();
+        json[1..json.len() - 1].to_string()
+    }
+
+    let value = |name: &str| match name {
+        "creator" => Some(escape(&notification.creator)),
+        "creator_id" => Some(escape(&notification.creator_id)),
+        "title" => Some(escape(&notification.title)),
+        "url" => Some(escape(&notification.url)),
+        "published" => Some(escape(&notification.published)),
+        "tags" => Some(escape(&notification.tags.join(", "))),
+        "files" => Some(notification.files.to_string()),
+        _ => None,
+    };
+
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let (before, token) = rest.split_at(start);
+        out.push_str(before);
+        let Some(end) = token.find("}}") else {
+            rest = token;
+            break;
+        };
+        match value(&token[2..end]) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&token[..end + 2]),
+        }
+        rest = &token[end + 2..];
+    }
+    out.push_str(rest);
+    out
+}